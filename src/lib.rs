@@ -1,24 +1,497 @@
-use std::{collections::BTreeMap, usize};
+pub mod parser;
+pub mod sql;
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Index;
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    /// Two relations an operator combines do not have compatible schemas
+    SchemaMismatch(String),
+    /// An attribute that is not part of the relation's schema
+    UnknownAttribute(String),
+    /// A relation referred to by a name that no relation has
+    UnknownRelation(String),
+    /// A relation added under a name another relation already has
+    DuplicateRelation(String),
+    /// An unqualified attribute name matching more than one attribute
+    AmbiguousAttribute(String),
+    /// Two attributes of a schema that would end up with the same name
+    DuplicateAttribute(String),
+    /// A computation whose result does not fit its type
+    Overflow(String),
+    /// A value whose type does not match the attribute it is used with
+    TypeMismatch(String),
+    /// A tuple whose primary key is already taken, by a stored tuple or
+    /// another tuple inserted with it
+    DuplicatePrimaryKey(String),
+    /// A tuple with NULL in one of its primary key attributes
+    NullPrimaryKey(String),
+    /// A lookup or insert into keyed data without the key it needs
+    MissingKey(String),
+    /// A division or remainder by zero
+    DivisionByZero,
+    /// Query text that does not parse, at a line and column counted from 1
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::SchemaMismatch(msg) => write!(f, "schema mismatch: {msg}"),
+            Error::UnknownAttribute(name) => write!(f, "unknown attribute {name}"),
+            Error::UnknownRelation(name) => write!(f, "unknown relation {name}"),
+            Error::DuplicateRelation(name) => write!(f, "duplicate relation {name}"),
+            Error::AmbiguousAttribute(name) => write!(f, "ambiguous attribute {name}"),
+            Error::DuplicateAttribute(name) => write!(f, "duplicate attribute {name}"),
+            Error::Overflow(msg) => write!(f, "overflow: {msg}"),
+            Error::TypeMismatch(msg) => write!(f, "type mismatch: {msg}"),
+            Error::DuplicatePrimaryKey(key) => write!(f, "duplicate primary key {key}"),
+            Error::NullPrimaryKey(name) => write!(f, "NULL in primary key attribute {name}"),
+            Error::MissingKey(msg) => write!(f, "missing key: {msg}"),
+            Error::DivisionByZero => write!(f, "division by zero"),
+            Error::Parse {
+                line,
+                column,
+                message,
+            } => write!(f, "parse error at {line}:{column}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Type {
     Str,
     Int,
+    Float,
+    /// An exact decimal with at most as many digits as the precision, of
+    /// which as many as the scale come after the point
+    Decimal(u32, u32),
+    Bool,
+    Date,
+    Timestamp,
+    Bytes,
 }
 
 #[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum Value {
     Str(String),
     Int(i64),
+    Float(Float),
+    Decimal(Decimal),
+    Bool(bool),
+    Date(Date),
+    Timestamp(Timestamp),
+    Bytes(Vec<u8>),
+    /// Absence of a value, allowed in nullable attributes. Orders after
+    /// every other value.
+    Null,
+}
+
+/// A 64 bit float with a total order, so that it can be part of a key.
+/// Follows [`f64::total_cmp`], where NaN comes after every number, except
+/// that -0.0 is the same as 0.0. Equality and hashing agree with it.
+#[derive(Debug, Clone, Copy)]
+pub struct Float(pub f64);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Float {}
+
+impl PartialOrd for Float {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Float {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // adding 0.0 turns -0.0 into 0.0 and leaves anything else alone
+        (self.0 + 0.0).total_cmp(&(other.0 + 0.0))
+    }
+}
+
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.0 + 0.0).to_bits().hash(state);
+    }
+}
+
+/// An exact decimal number, `mantissa / 10^scale`. Equal numbers compare
+/// and hash the same whatever their scale, so 1.5 and 1.50 are the same key.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u32,
+}
+
+impl Decimal {
+    /// Digits an `i128` mantissa always holds
+    pub const MAX_PRECISION: u32 = 38;
+
+    /// Digits after the point a quotient keeps at the least
+    pub const DIVISION_SCALE: u32 = 6;
+
+    pub fn new(mantissa: i128, scale: u32) -> Decimal {
+        Decimal { mantissa, scale }
+    }
+
+    pub fn mantissa(&self) -> i128 {
+        self.mantissa
+    }
+
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// Whether the number has at most `scale` digits after the point and
+    /// `precision` digits in all once written with that scale
+    pub fn fits(&self, precision: u32, scale: u32) -> bool {
+        let normalized = self.normalized();
+        normalized.scale <= scale
+            && normalized.rescale(scale).is_some_and(|d| {
+                d.mantissa.unsigned_abs().checked_ilog10().unwrap_or(0) < precision
+            })
+    }
+
+    pub fn checked_add(self, other: Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        let (left, right) = (self.rescale(scale)?, other.rescale(scale)?);
+        Some(Decimal::new(
+            left.mantissa.checked_add(right.mantissa)?,
+            scale,
+        ))
+    }
+
+    pub fn checked_sub(self, other: Decimal) -> Option<Decimal> {
+        self.checked_add(Decimal::new(other.mantissa.checked_neg()?, other.scale))
+    }
+
+    pub fn checked_rem(self, other: Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        let (left, right) = (self.rescale(scale)?, other.rescale(scale)?);
+        Some(Decimal::new(
+            left.mantissa.checked_rem(right.mantissa)?,
+            scale,
+        ))
+    }
+
+    pub fn checked_mul(self, other: Decimal) -> Option<Decimal> {
+        Some(Decimal::new(
+            self.mantissa.checked_mul(other.mantissa)?,
+            self.scale + other.scale,
+        ))
+    }
+
+    /// The quotient with the larger scale of the two, and at least
+    /// [`Decimal::DIVISION_SCALE`], rounded half away from zero
+    pub fn checked_div(self, other: Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale).max(Decimal::DIVISION_SCALE);
+        let factor = 10i128.checked_pow(scale + other.scale - self.scale)?;
+        let dividend = self.mantissa.checked_mul(factor)?;
+        let quotient = dividend.checked_div(other.mantissa)?;
+        let remainder = dividend % other.mantissa;
+
+        let mantissa = if remainder.unsigned_abs() * 2 >= other.mantissa.unsigned_abs() {
+            quotient.checked_add(dividend.signum() * other.mantissa.signum())?
+        } else {
+            quotient
+        };
+
+        Some(Decimal::new(mantissa, scale))
+    }
+
+    /// The nearest float, which is not exact
+    pub fn to_f64(self) -> f64 {
+        self.mantissa as f64 / 10f64.powi(self.scale as i32)
+    }
+
+    /// The same number without trailing zeros after the point
+    fn normalized(self) -> Decimal {
+        let mut d = self;
+        while d.scale > 0 && d.mantissa % 10 == 0 {
+            d.mantissa /= 10;
+            d.scale -= 1;
+        }
+
+        d
+    }
+
+    /// The same number with a larger scale, if the mantissa still fits
+    fn rescale(self, scale: u32) -> Option<Decimal> {
+        let factor = 10i128.checked_pow(scale.checked_sub(self.scale)?)?;
+        Some(Decimal::new(self.mantissa.checked_mul(factor)?, scale))
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let (left, right) = (self.normalized(), other.normalized());
+        let scale = left.scale.max(right.scale);
+
+        // only the side with the smaller scale is scaled up, and when that
+        // overflows it is the larger one in magnitude
+        match (left.rescale(scale), right.rescale(scale)) {
+            (Some(l), Some(r)) => l.mantissa.cmp(&r.mantissa),
+            (None, _) => 0.cmp(&left.mantissa).reverse(),
+            (_, None) => 0.cmp(&right.mantissa),
+        }
+    }
+}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let normalized = self.normalized();
+        normalized.mantissa.hash(state);
+        normalized.scale.hash(state);
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = Error;
+
+    /// Parses numbers such as `12`, `-0.50` or `+3.125`, keeping the scale
+    /// as written
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::TypeMismatch(format!("{s} is not a decimal"));
+
+        let digits = s.strip_prefix(['+', '-']).unwrap_or(s);
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        if int.is_empty() && frac.is_empty()
+            || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        let mantissa = format!("{int}{frac}")
+            .parse::<i128>()
+            .map_err(|_| Error::Overflow(format!("{s} does not fit a decimal")))?;
+        let mantissa = if s.starts_with('-') {
+            -mantissa
+        } else {
+            mantissa
+        };
+
+        Ok(Decimal::new(mantissa, frac.len() as u32))
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let digits = format!("{digits:0>width$}", width = self.scale as usize + 1);
+        let (int, frac) = digits.split_at(digits.len() - self.scale as usize);
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+
+        match frac.is_empty() {
+            true => write!(f, "{sign}{int}"),
+            false => write!(f, "{sign}{int}.{frac}"),
+        }
+    }
+}
+
+/// A day of the proleptic Gregorian calendar, as days since 1970-01-01
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct Date(pub i32);
+
+impl Date {
+    /// The date of a year, month and day, if there is one
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Option<Date> {
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+            return None;
+        }
+
+        // days from civil, see http://howardhinnant.github.io/date_algorithms.html
+        let (month, day) = (month as i64, day as i64);
+        let year = year as i64 - (month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let yoe = year - era * 400;
+        let doy = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let date = Date(i32::try_from(era * 146_097 + doe - 719_468).ok()?);
+
+        // days past the end of the month roll over into the next one
+        (date.ymd() == (year as i32 + (month <= 2) as i32, month as u32, day as u32))
+            .then_some(date)
+    }
+
+    /// The year, month and day of the date
+    pub fn ymd(self) -> (i32, u32, u32) {
+        let days = self.0 as i64 + 719_468;
+        let era = days.div_euclid(146_097);
+        let doe = days - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+
+        (year as i32, month as u32, day as u32)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{year:04}-{month:02}-{day:02}")
+    }
+}
+
+impl FromStr for Date {
+    type Err = Error;
+
+    /// Parses dates written as `YYYY-MM-DD`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '-');
+        let mut part = || parts.next().map(str::parse::<u32>);
+        match (part(), part(), part()) {
+            (Some(Ok(year)), Some(Ok(month)), Some(Ok(day))) => i32::try_from(year)
+                .ok()
+                .and_then(|year| Date::from_ymd(year, month, day)),
+            _ => None,
+        }
+        .ok_or_else(|| Error::TypeMismatch(format!("{s} is not a date")))
+    }
+}
+
+/// A point in time, as microseconds since 1970-01-01 00:00:00 UTC
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct Timestamp(pub i64);
+
+const MICROS_PER_DAY: i64 = 86_400_000_000;
+
+impl Timestamp {
+    /// The timestamp of a time of day on `date`, if the time exists and
+    /// the timestamp fits
+    pub fn new(date: Date, hour: u32, minute: u32, second: u32, micros: u32) -> Option<Timestamp> {
+        if hour > 23 || minute > 59 || second > 59 || micros > 999_999 {
+            return None;
+        }
+
+        let time = ((hour * 60 + minute) * 60 + second) as i64 * 1_000_000 + micros as i64;
+        (date.0 as i64)
+            .checked_mul(MICROS_PER_DAY)?
+            .checked_add(time)
+            .map(Timestamp)
+    }
+
+    pub fn date(self) -> Date {
+        Date(self.0.div_euclid(MICROS_PER_DAY) as i32)
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = Error;
+
+    /// Parses timestamps written as `YYYY-MM-DD HH:MM:SS`, with up to six
+    /// digits of a fraction of a second
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::TypeMismatch(format!("{s} is not a timestamp"));
+
+        let (date, time) = s.split_once([' ', 'T']).ok_or_else(invalid)?;
+        let (time, fraction) = time.split_once('.').unwrap_or((time, ""));
+        if fraction.len() > 6 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let mut parts = time.split(':').map(|part| part.parse::<u32>().ok());
+        let (Some(Some(hour)), Some(Some(minute)), Some(Some(second)), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let micros = format!("{fraction:0<6}")
+            .parse::<u32>()
+            .map_err(|_| invalid())?;
+
+        Timestamp::new(date.parse()?, hour, minute, second, micros).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let time = self.0.rem_euclid(MICROS_PER_DAY);
+        let (seconds, micros) = (time / 1_000_000, time % 1_000_000);
+        write!(
+            f,
+            "{} {:02}:{:02}:{:02}",
+            self.date(),
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )?;
+        if micros != 0 {
+            write!(f, ".{micros:06}")?;
+        }
+
+        Ok(())
+    }
 }
 
 pub type Row = Vec<Value>;
 // have a row type as an iterable, inspired by toydb
 
+/// Values of the primary key attributes of a row, in key order
+pub type Key = Vec<Value>;
+
+/// The values of a row by position, which predicates and expressions are
+/// evaluated against
+pub trait Fields: Index<usize, Output = Value> {}
+
+impl Fields for Row {}
+
+/// A pair of tuples a join looks at, read as the row they would make
+/// without copying them into one
+struct Joined<'a>(&'a [Value], &'a [Value]);
+
+impl Index<usize> for Joined<'_> {
+    type Output = Value;
+
+    fn index(&self, i: usize) -> &Value {
+        match self.0.get(i) {
+            Some(value) => value,
+            None => &self.1[i - self.0.len()],
+        }
+    }
+}
+
+impl Fields for Joined<'_> {}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Attribute {
     name: String,
     atype: Type,
+    /// Whether the attribute may hold `Value::Null`
+    nullable: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -26,89 +499,279 @@ pub struct Schema {
     attributes: Vec<Attribute>,
 }
 
+impl Attribute {
+    /// An attribute that may not hold NULL
+    pub fn new(name: impl Into<String>, atype: Type) -> Self {
+        Attribute {
+            name: name.into(),
+            atype,
+            nullable: false,
+        }
+    }
+
+    /// The same attribute, allowed to hold NULL
+    pub fn nullable(self) -> Self {
+        Attribute {
+            nullable: true,
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn atype(&self) -> &Type {
+        &self.atype
+    }
+
+    pub fn is_nullable(&self) -> bool {
+        self.nullable
+    }
+
+    /// Prefixes the name with the relation it comes from, `users.id`, unless
+    /// it is already qualified
+    fn qualified(&self, relation: &str) -> Attribute {
+        if self.name.contains('.') {
+            return self.clone();
+        }
+
+        Attribute {
+            name: format!("{}.{}", relation, self.name),
+            atype: self.atype.clone(),
+            nullable: self.nullable,
+        }
+    }
+}
+
 impl PartialEq<Value> for Type {
     fn eq(&self, other: &Value) -> bool {
-        match (self, other) {
-            (Type::Str, Value::Str(_)) => true,
-            (Type::Int, Value::Int(_)) => true,
-            _ => false,
+        if let (Type::Decimal(precision, scale), Value::Decimal(d)) = (self, other) {
+            return d.fits(*precision, *scale);
         }
+
+        matches!(
+            (self, other),
+            (Type::Str, Value::Str(_))
+                | (Type::Int, Value::Int(_))
+                | (Type::Float, Value::Float(_))
+                | (Type::Bool, Value::Bool(_))
+                | (Type::Date, Value::Date(_))
+                | (Type::Timestamp, Value::Timestamp(_))
+                | (Type::Bytes, Value::Bytes(_))
+        )
     }
 }
 
 impl Schema {
+    /// A schema of `attributes`, which must have different names
+    pub fn new(attributes: Vec<Attribute>) -> Result<Self, Error> {
+        let mut names = HashSet::new();
+        if let Some(a) = attributes.iter().find(|a| !names.insert(&a.name)) {
+            return Err(Error::DuplicateAttribute(a.name.clone()));
+        }
+
+        Ok(Schema { attributes })
+    }
+
+    pub fn attributes(&self) -> &[Attribute] {
+        &self.attributes
+    }
+
     pub fn validate_row(&self, row: &Row) -> bool {
+        self.check_row(row).is_ok()
+    }
+
+    /// Like [`Schema::validate_row`], but says what is wrong with the row
+    fn check_row(&self, row: &Row) -> Result<(), Error> {
         if row.len() != self.attributes.len() {
-            return false;
+            return Err(Error::SchemaMismatch(format!(
+                "row has {} values, schema has {} attributes",
+                row.len(),
+                self.attributes.len()
+            )));
         }
 
-        let res = self
+        match self
             .attributes
             .iter()
             .zip(row.iter())
-            .all(|(a, b)| a.atype == *b);
+            .find(|(a, b)| !(a.atype == **b || (a.nullable && **b == Value::Null)))
+        {
+            Some((a, Value::Decimal(d))) if matches!(a.atype, Type::Decimal(..)) => Err(
+                Error::Overflow(format!("{d} does not fit {} {:?}", a.name, a.atype)),
+            ),
+            Some((a, b)) => Err(Error::TypeMismatch(format!(
+                "{:?} for {} {:?}",
+                b, a.name, a.atype
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Index of `attr` in the schema. An unqualified name such as `id` also
+    /// resolves to a qualified `users.id`, as long as only one attribute
+    /// matches.
+    pub fn position(&self, attr: &Attribute) -> Result<usize, Error> {
+        self.find(&attr.name, Some(&attr.atype))
+    }
+
+    /// Index of the attribute called `name` whatever its type, resolved the
+    /// same way as [`Schema::position`]
+    pub fn index_of(&self, name: &str) -> Result<usize, Error> {
+        self.find(name, None)
+    }
+
+    fn find(&self, name: &str, atype: Option<&Type>) -> Result<usize, Error> {
+        let typed = |x: &Attribute| atype.is_none_or(|t| same_kind(&x.atype, t));
+
+        if let Some(index) = self
+            .attributes
+            .iter()
+            .position(|x| x.name == name && typed(x))
+        {
+            return Ok(index);
+        }
+
+        let suffix = format!(".{}", name);
+        let mut matches = self
+            .attributes
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| typed(x) && x.name.ends_with(&suffix));
+
+        match (matches.next(), matches.next()) {
+            (Some((index, _)), None) => Ok(index),
+            (Some(_), Some(_)) => Err(Error::AmbiguousAttribute(name.to_string())),
+            _ => Err(Error::UnknownAttribute(name.to_string())),
+        }
+    }
+
+    /// Two schemas are union-compatible when they have the same arity and
+    /// the attributes at each position share a type, DECIMALs of any
+    /// precision and scale included. Names may differ.
+    pub fn union_compatible(&self, other: &Schema) -> Result<(), Error> {
+        if self.attributes.len() != other.attributes.len() {
+            return Err(Error::SchemaMismatch(format!(
+                "arity {} does not match arity {}",
+                self.attributes.len(),
+                other.attributes.len()
+            )));
+        }
+
+        for (index, (a, b)) in self.attributes.iter().zip(&other.attributes).enumerate() {
+            if !same_kind(&a.atype, &b.atype) {
+                return Err(Error::SchemaMismatch(format!(
+                    "attribute {index} is {} {:?} on one side and {} {:?} on the other",
+                    a.name, a.atype, b.name, b.atype
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The schema of the union of relations of this schema and `other`,
+    /// with the names of this one. An attribute is nullable when it is on
+    /// either side, and a DECIMAL holds the digits of both sides.
+    fn union(&self, other: &Schema) -> Schema {
+        let attributes = self
+            .attributes
+            .iter()
+            .zip(&other.attributes)
+            .map(|(a, b)| Attribute {
+                atype: match (&a.atype, &b.atype) {
+                    (Type::Decimal(lp, ls), Type::Decimal(rp, rs)) => {
+                        let scale = *ls.max(rs);
+                        let digits = (lp - ls).max(rp - rs);
+                        Type::Decimal((digits + scale).min(Decimal::MAX_PRECISION), scale)
+                    }
+                    (atype, _) => atype.clone(),
+                },
+                nullable: a.nullable || b.nullable,
+                ..a.clone()
+            })
+            .collect();
 
-        return res;
+        Schema { attributes }
     }
 }
 
 #[derive(Debug, Clone)]
 pub enum Data {
-    WithPK(Box<BTreeMap<Value, Row>>),
+    WithPK(Box<BTreeMap<Key, Row>>),
     NoPK((i32, Box<BTreeMap<i32, Row>>)),
+    /// Tuples in a guaranteed order, along with their key when the relation
+    /// has one. Produced by sorting and kept by the operators that do not
+    /// reorder tuples. The keys are also kept in a set, to find a key that
+    /// is taken without a scan.
+    Ordered(Box<Vec<(Option<Key>, Row)>>, HashSet<Key>),
 }
 
 impl Data {
-    pub fn insert(&mut self, key: Option<Value>, row: Row) -> bool {
+    /// Stores `row`, under `key` for keyed data. Fails if keyed data is
+    /// given no key or a key that is already taken.
+    pub fn insert(&mut self, key: Option<Key>, row: Row) -> Result<(), Error> {
         match self {
             Data::WithPK(tree) => {
-                if key.is_none() {
-                    println!("[PANIC] key not provided while inserting BTree");
-                    return false;
+                let key = key.ok_or(Error::MissingKey(
+                    "no key given to insert into keyed data".to_string(),
+                ))?;
+                if tree.contains_key(&key) {
+                    return Err(Error::DuplicatePrimaryKey(format!("{key:?}")));
                 }
 
-                _ = tree.insert(key.unwrap(), row);
-                return true;
+                tree.insert(key, row);
             }
             Data::NoPK((key, tree)) => {
                 tree.insert(*key, row);
                 *key += 1;
-                return true;
+            }
+            Data::Ordered(rows, keys) => {
+                if let Some(key) = &key
+                    && !keys.insert(key.clone())
+                {
+                    return Err(Error::DuplicatePrimaryKey(format!("{key:?}")));
+                }
+
+                rows.push((key, row));
             }
         }
+
+        Ok(())
     }
 
-    pub fn contains(&self, key: Option<Value>, row: Option<Row>) -> bool {
+    /// Whether a tuple with `key` is stored, or for data without keys, a
+    /// tuple equal to `row`
+    pub fn contains(&self, key: Option<Key>, row: Option<Row>) -> Result<bool, Error> {
         match self {
-            Data::WithPK(tree) => {
-                if key.is_none() {
-                    println!("[DEBUG] {:?}", &self);
-                    println!("[PANIC] key not provided while querying BTree");
-                    return false;
-                }
-                tree.contains_key(&key.unwrap())
-            }
-            Data::NoPK((_, tree)) => {
-                if row.is_none() {
-                    println!("[DEBUG] {:?}", &self);
-                    println!("[PANIC] row not provided while querying BTree");
-                    return false;
-                }
-                tree.values().any(|v| v == row.as_ref().unwrap())
-            }
+            Data::WithPK(tree) => match key {
+                Some(key) => Ok(tree.contains_key(&key)),
+                None => Err(Error::MissingKey(
+                    "no key given to look up keyed data".to_string(),
+                )),
+            },
+            Data::NoPK((_, tree)) => match row {
+                Some(row) => Ok(tree.values().any(|v| *v == row)),
+                None => Err(Error::MissingKey(
+                    "no row given to look up data without keys".to_string(),
+                )),
+            },
+            Data::Ordered(rows, keys) => match (key, row) {
+                (Some(key), _) => Ok(keys.contains(&key)),
+                (None, Some(row)) => Ok(rows.iter().any(|(_, r)| *r == row)),
+                (None, None) => Err(Error::MissingKey(
+                    "neither a key nor a row given to look up".to_string(),
+                )),
+            },
         }
     }
 
     pub fn tuples(&self) -> Vec<Row> {
         match self {
-            Data::WithPK(tree) => Vec::from_iter(tree.values())
-                .into_iter()
-                .map(|inner| inner.clone())
-                .collect(),
-            Data::NoPK((_, tree)) => Vec::from_iter(tree.values())
-                .into_iter()
-                .map(|inner| inner.clone())
-                .collect(),
+            Data::WithPK(tree) => Vec::from_iter(tree.values()).into_iter().cloned().collect(),
+            Data::NoPK((_, tree)) => Vec::from_iter(tree.values()).into_iter().cloned().collect(),
+            Data::Ordered(rows, _) => rows.iter().map(|(_, row)| row.clone()).collect(),
         }
     }
 }
@@ -117,7 +780,9 @@ impl Data {
 #[allow(unused)]
 pub struct Relation {
     name: String,
-    pk: Option<usize>,
+    /// Positions of the primary key attributes, more than one for a
+    /// composite key
+    pk: Option<Vec<usize>>,
     // fks: Option<Vec<usize>>,
     schema: Schema,
 
@@ -126,83 +791,243 @@ pub struct Relation {
 }
 
 impl Relation {
-    pub fn insert_row(&mut self, row: Row) -> bool {
-        if !self.schema.validate_row(&row) {
-            return false;
+    /// Starts a relation called `name`, with attributes and a primary key to
+    /// add before [`RelationBuilder::build`]
+    pub fn builder(name: impl Into<String>) -> RelationBuilder {
+        RelationBuilder {
+            name: name.into(),
+            attributes: vec![],
+            pk: vec![],
         }
+    }
 
-        if self.pk.is_none() {
-            // insert regardless
-            return self.data.insert(None, row);
-        }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
 
-        if self
-            .data
-            .contains(Some(row[self.pk.unwrap()].clone()), None)
-        {
-            return false;
-        }
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Positions of the primary key attributes in the schema
+    pub fn primary_key(&self) -> Option<&[usize]> {
+        self.pk.as_deref()
+    }
+
+    pub fn tuples(&self) -> Vec<Row> {
+        self.data.tuples()
+    }
 
-        _ = self.data.insert(Some(row[self.pk.unwrap()].clone()), row);
+    pub fn insert_row(&mut self, row: Row) -> Result<(), Error> {
+        self.schema.check_row(&row)?;
+        self.check_key(&row)?;
 
-        true
+        // without a primary key, insert regardless
+        self.data.insert(self.key(&row), row)
     }
 
-    pub fn insert_rows(&mut self, rows: Vec<Row>) -> bool {
-        if !rows.iter().all(|r| self.schema.validate_row(r)) {
-            println!("[ERROR] rows are not valid, not inserting - INSERT ROWS");
-            return false;
+    /// Inserts all of `rows`, or none of them if any is invalid or repeats a
+    /// primary key
+    pub fn insert_rows(&mut self, rows: Vec<Row>) -> Result<(), Error> {
+        for row in &rows {
+            self.schema.check_row(row)?;
+            self.check_key(row)?;
         }
 
-        if self.pk.is_none() {
-            // insert all rows even if there are duplicates
-            for row in rows {
-                _ = self.data.insert(None, row);
+        if self.pk.is_some() {
+            // rows are dup because of primary key repetitions, among
+            // themselves or with what is stored
+            let mut keys = HashSet::new();
+            for key in rows.iter().filter_map(|r| self.key(r)) {
+                if !keys.insert(key.clone()) || self.data.contains(Some(key.clone()), None)? {
+                    return Err(Error::DuplicatePrimaryKey(format!("{key:?}")));
+                }
             }
-            return true;
         }
 
-        // rows if dup because of primary key repeations
-        let nondup_rows = rows
-            .iter()
-            .map(|r| r[self.pk.unwrap()].clone())
-            .collect::<std::collections::HashSet<Value>>()
-            .len();
+        for row in rows {
+            self.data.insert(self.key(&row), row)?;
+        }
 
-        if nondup_rows != rows.len() {
-            println!(
-                "[ERROR] there are repeats in the primary key used, not inserting - INSERT ROWS"
-            );
-            return false;
+        Ok(())
+    }
+
+    /// Primary key attributes never hold NULL, even when they are nullable
+    fn check_key(&self, row: &Row) -> Result<(), Error> {
+        match self.pk.iter().flatten().find(|i| row[**i] == Value::Null) {
+            Some(i) => Err(Error::NullPrimaryKey(
+                self.schema.attributes[*i].name.clone(),
+            )),
+            None => Ok(()),
         }
+    }
 
-        let new_data = rows
-            .iter()
-            .all(|r| self.data.contains(Some(r[self.pk.unwrap()].clone()), None) == false);
-        if !new_data {
-            println!("[DEBUG] {:?}", &self);
-            println!("[ERROR] insert rows failed - INSERT ROWS");
-            return false;
+    /// The primary key of `row`, if the relation has one
+    fn key(&self, row: &Row) -> Option<Key> {
+        self.pk
+            .as_ref()
+            .map(|pk| pk.iter().map(|i| row[*i].clone()).collect())
+    }
+
+    /// An empty relation, backed by a BTree keyed on `pk` when there is one
+    fn empty(name: String, pk: Option<Vec<usize>>, schema: Schema) -> Relation {
+        let data = match pk {
+            Some(_) => Data::WithPK(Box::default()),
+            None => Data::NoPK((0, Box::default())),
+        };
+
+        Relation {
+            name,
+            pk,
+            schema,
+            data,
         }
+    }
 
-        for row in rows {
-            _ = self.data.insert(Some(row[self.pk.unwrap()].clone()), row);
+    /// An empty relation to hold the result of an operator
+    fn derived(pk: Option<Vec<usize>>, schema: Schema) -> Relation {
+        Relation::empty("derived".to_string(), pk, schema)
+    }
+
+    /// Like [`Relation::derived`], for an operator that keeps the identity
+    /// of `source`. The result is named after it, so that its attributes
+    /// are still qualified by that name in a join, and keeps tuples in the
+    /// order they are inserted when `source` is ordered.
+    fn derived_from(source: &Relation, pk: Option<Vec<usize>>, schema: Schema) -> Relation {
+        let mut derived = Relation::empty(source.name.clone(), pk, schema);
+        if let Data::Ordered(..) = source.data {
+            derived.data = Data::Ordered(Box::default(), HashSet::new());
         }
 
-        return true;
+        derived
     }
 
-    // this is being used in tests
-    // #[allow(dead_code)]
-    // fn get_tuples(&self) -> Vec<Row> {
-    //     Vec::from_iter(self.data.values())
-    //         .into_iter()
-    //         .map(|inner| inner.clone())
+    /// Adds `attr` after the other attributes, NULL in every stored tuple
+    pub fn add_attribute(&mut self, attr: Attribute) -> Result<(), Error> {
+        if self.schema.attributes.iter().any(|a| a.name == attr.name) {
+            return Err(Error::DuplicateAttribute(attr.name));
+        }
+
+        let mut schema = self.schema.clone();
+        schema.attributes.push(attr);
+        self.restructure(self.pk.clone(), schema, |mut row| {
+            row.push(Value::Null);
+            row
+        })
+    }
+
+    /// Removes the attribute called `name` from the schema and every stored
+    /// tuple. Removing an attribute of the primary key removes the key.
+    pub fn drop_attribute(&mut self, name: &str) -> Result<(), Error> {
+        let index = self.schema.index_of(name)?;
+
+        let pk = match &self.pk {
+            Some(pk) if !pk.contains(&index) => Some(
+                pk.iter()
+                    .map(|i| if *i > index { i - 1 } else { *i })
+                    .collect(),
+            ),
+            _ => None,
+        };
+        let mut schema = self.schema.clone();
+        schema.attributes.remove(index);
+        self.restructure(pk, schema, |mut row| {
+            row.remove(index);
+            row
+        })
+    }
+
+    pub fn rename_attribute(&mut self, from: &str, to: &str) -> Result<(), Error> {
+        let index = self.schema.index_of(from)?;
+        if self.schema.attributes.iter().any(|a| a.name == to) {
+            return Err(Error::DuplicateAttribute(to.to_string()));
+        }
+
+        self.schema.attributes[index].name = to.to_string();
+        Ok(())
+    }
+
+    /// Replaces the primary key and the schema, with every stored tuple
+    /// rewritten to fit them, or changes nothing if a tuple does not fit
+    fn restructure(
+        &mut self,
+        pk: Option<Vec<usize>>,
+        schema: Schema,
+        rewrite: impl Fn(Row) -> Row,
+    ) -> Result<(), Error> {
+        let mut relation = Relation::empty(self.name.clone(), pk, schema);
+        relation.insert_rows(self.data.tuples().into_iter().map(rewrite).collect())?;
+        *self = relation;
+
+        Ok(())
+    }
+
+    // this is being used in tests
+    // #[allow(dead_code)]
+    // fn get_tuples(&self) -> Vec<Row> {
+    //     Vec::from_iter(self.data.values())
+    //         .into_iter()
+    //         .map(|inner| inner.clone())
     //         .collect::<Vec<Row>>()
     // }
 }
 
+/// Builds a [`Relation`] from its attributes and primary key, which picks
+/// how the relation stores its tuples
 #[derive(Debug)]
+pub struct RelationBuilder {
+    name: String,
+    attributes: Vec<Attribute>,
+    pk: Vec<String>,
+}
+
+impl RelationBuilder {
+    /// Adds an attribute that may not hold NULL
+    pub fn column(self, name: impl Into<String>, atype: Type) -> Self {
+        self.attribute(Attribute::new(name, atype))
+    }
+
+    /// Adds an attribute that may hold NULL
+    pub fn nullable_column(self, name: impl Into<String>, atype: Type) -> Self {
+        self.attribute(Attribute::new(name, atype).nullable())
+    }
+
+    pub fn attribute(mut self, attr: Attribute) -> Self {
+        self.attributes.push(attr);
+        self
+    }
+
+    /// Adds the attribute called `name` to the primary key, after the ones
+    /// already in it
+    pub fn primary_key(mut self, name: impl Into<String>) -> Self {
+        self.pk.push(name.into());
+        self
+    }
+
+    /// The empty relation, keyed when it has a primary key. Primary key
+    /// attributes never hold NULL, even when they are added as nullable.
+    pub fn build(self) -> Result<Relation, Error> {
+        let mut schema = Schema::new(self.attributes)?;
+
+        let mut pk = vec![];
+        for name in &self.pk {
+            let index = match schema.attributes.iter().position(|a| a.name == *name) {
+                Some(index) => index,
+                None => return Err(Error::UnknownAttribute(name.clone())),
+            };
+            if pk.contains(&index) {
+                return Err(Error::DuplicateAttribute(name.clone()));
+            }
+            schema.attributes[index].nullable = false;
+            pk.push(index);
+        }
+
+        let pk = (!pk.is_empty()).then_some(pk);
+        Ok(Relation::empty(self.name, pk, schema))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Comp {
     /// Greater than
     GT,
@@ -216,6 +1041,58 @@ pub enum Comp {
     EQ,
     /// Not-Equal To
     NE,
+    /// Is NULL, the operand is ignored
+    IsNull,
+    /// Is not NULL, the operand is ignored
+    IsNotNull,
+}
+
+/// SQL's three-valued logic, where a comparison with NULL is neither true
+/// nor false but unknown
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Truth {
+    True,
+    False,
+    Unknown,
+}
+
+impl Truth {
+    pub fn and(self, other: Truth) -> Truth {
+        match (self, other) {
+            (Truth::False, _) | (_, Truth::False) => Truth::False,
+            (Truth::True, Truth::True) => Truth::True,
+            _ => Truth::Unknown,
+        }
+    }
+
+    pub fn or(self, other: Truth) -> Truth {
+        match (self, other) {
+            (Truth::True, _) | (_, Truth::True) => Truth::True,
+            (Truth::False, Truth::False) => Truth::False,
+            _ => Truth::Unknown,
+        }
+    }
+}
+
+impl std::ops::Not for Truth {
+    type Output = Truth;
+
+    fn not(self) -> Truth {
+        match self {
+            Truth::True => Truth::False,
+            Truth::False => Truth::True,
+            Truth::Unknown => Truth::Unknown,
+        }
+    }
+}
+
+impl From<bool> for Truth {
+    fn from(value: bool) -> Self {
+        match value {
+            true => Truth::True,
+            false => Truth::False,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -226,567 +1103,5197 @@ pub enum Connective {
     OR,
 }
 
+/// Right hand side of a condition, either a constant or another attribute
+/// of the same row
+#[derive(Debug)]
+pub enum Operand {
+    Value(Value),
+    Attr(Attribute),
+}
+
+/// A condition on a row. Conditions link into a chain through their
+/// connectives, where AND binds tighter than OR, and And, Or and Not nodes
+/// group predicates into a tree. A tree node in a chain counts as a single,
+/// parenthesized condition, so `a AND (b OR c)` is
+/// `Condition(a, Some((AND, Or(b, c))))`.
 #[derive(Debug)]
 pub enum SelPredicate {
     Condition(
-        (Attribute, Comp, Value),
+        (Attribute, Comp, Operand),
         Option<(Connective, Box<SelPredicate>)>,
     ),
+    /// A condition given as an expression, which must give a BOOL
+    Expr(Expr, Option<(Connective, Box<SelPredicate>)>),
+    /// Both predicates hold
+    And(Box<SelPredicate>, Box<SelPredicate>),
+    /// Either predicate holds
+    Or(Box<SelPredicate>, Box<SelPredicate>),
+    /// The predicate does not hold, and stays unknown when it is unknown
+    Not(Box<SelPredicate>),
     None,
 }
 
-impl SelPredicate {
-    pub fn validate(&self) -> bool {
+impl Comp {
+    /// Compares two values. NULL is not comparable, so any comparison with
+    /// it is unknown, except for IS NULL and IS NOT NULL which only look at
+    /// `left`.
+    pub fn compare(&self, left: &Value, right: &Value) -> Truth {
         match self {
-            SelPredicate::Condition((_, _, _), _) => {
-                unimplemented!()
-            }
-            SelPredicate::None => {
-                // this is equivalent to
-            }
+            Comp::IsNull => return (*left == Value::Null).into(),
+            Comp::IsNotNull => return (*left != Value::Null).into(),
+            _ => {}
+        }
+
+        if *left == Value::Null || *right == Value::Null {
+            return Truth::Unknown;
         }
 
-        return false;
+        match self {
+            Comp::GT => left > right,
+            Comp::LT => left < right,
+            Comp::GE => left >= right,
+            Comp::LE => left <= right,
+            Comp::EQ => left == right,
+            Comp::NE => left != right,
+            Comp::IsNull | Comp::IsNotNull => unreachable!(),
+        }
+        .into()
     }
 }
 
-#[derive(Debug)]
-pub enum ProjAttrs {
-    Attr(Attribute, Option<Box<ProjAttrs>>),
-    None,
+/// Arithmetic and string operators of an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    /// Division, truncated for INTs and rounded to at least
+    /// [`Decimal::DIVISION_SCALE`] digits after the point for DECIMALs
+    Div,
+    /// Remainder of the division
+    Mod,
+    /// String concatenation
+    Concat,
 }
 
-struct ProjAttrIterator<'a> {
-    current: &'a ProjAttrs,
+/// Functions an expression can call
+#[derive(Debug, Clone, PartialEq)]
+pub enum Function {
+    /// Absolute value of a number
+    Abs,
+    Lower,
+    Upper,
+    /// Characters in a STR, or bytes in BYTES
+    Length,
+    /// The first argument that is not NULL
+    Coalesce,
 }
 
-impl ProjAttrs {
-    fn iter(&self) -> ProjAttrIterator<'_> {
-        ProjAttrIterator { current: self }
+/// A scalar expression over the attributes of a row. NULL in any operand
+/// gives NULL, and a comparison gives a BOOL, or NULL when it is unknown.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// An attribute by name, resolved like [`Schema::index_of`]
+    Attr(String),
+    Value(Value),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, BinOp, Box<Expr>),
+    Compare(Box<Expr>, Comp, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+impl Expr {
+    /// The type of the values the expression gives for rows of `schema`,
+    /// or an error if it does not type check
+    pub fn infer_type(&self, schema: &Schema) -> Result<Type, Error> {
+        self.infer(schema)?.ok_or_else(|| {
+            Error::TypeMismatch(format!(
+                "the type of {self:?} is unknown, it is always NULL"
+            ))
+        })
     }
 
-    pub fn execute(&self, relation: &Relation) -> Option<Relation> {
-        // println!("[Projection] query {:?}", self);
-        match self {
-            ProjAttrs::None => {
-                // Same as SELECT * FROM relation
-                println!("[DEBUG][Projection] Query : Select *, returning all tuples");
-                let values = relation
-                    .data
-                    .tuples()
-                    .into_iter()
-                    .collect::<std::collections::HashSet<Row>>()
+    /// Like [`Expr::infer_type`], with no type for a NULL literal, which
+    /// goes with any other type
+    fn infer(&self, schema: &Schema) -> Result<Option<Type>, Error> {
+        let mismatch = || Error::TypeMismatch(format!("{self:?}"));
+
+        let atype = match self {
+            Expr::Attr(name) => Some(schema.attributes[schema.index_of(name)?].atype.clone()),
+            Expr::Value(value) => value_type(value),
+            Expr::Neg(expr) => match expr.infer(schema)? {
+                Some(t) if !is_numeric(&t) => return Err(mismatch()),
+                t => t,
+            },
+            Expr::Binary(left, op, right) => match (left.infer(schema)?, right.infer(schema)?) {
+                (Some(l), Some(r)) if *op == BinOp::Concat => {
+                    (l == Type::Str && r == Type::Str).then_some(Some(l))
+                }
+                (Some(l), Some(r)) => arithmetic_type(op, &l, &r).map(Some),
+                (Some(t), None) | (None, Some(t)) if *op == BinOp::Concat => {
+                    (t == Type::Str).then_some(Some(t))
+                }
+                (Some(t), None) | (None, Some(t)) => arithmetic_type(op, &t, &t).map(Some),
+                (None, None) => Some(None),
+            }
+            .ok_or_else(mismatch)?,
+            Expr::Compare(left, comp, right) => {
+                let left = left.infer(schema)?;
+                if !matches!(comp, Comp::IsNull | Comp::IsNotNull)
+                    && let (Some(l), Some(r)) = (&left, right.infer(schema)?)
+                    && !comparable(l, &r)
+                {
+                    return Err(mismatch());
+                }
+                Some(Type::Bool)
+            }
+            Expr::Call(function, args) => {
+                let types = args
                     .iter()
-                    .map(|row| row.clone())
-                    .collect::<Vec<Row>>();
-
-                let mut derived = Relation {
-                    name: "derived".to_string(),
-                    pk: relation.pk,
-                    schema: relation.schema.clone(),
-                    data: {
-                        if relation.pk.is_some() {
-                            Data::WithPK(Box::new(BTreeMap::new()))
-                        } else {
-                            Data::NoPK((0, Box::new(BTreeMap::new())))
+                    .map(|arg| arg.infer(schema))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match (function, &types[..]) {
+                    (Function::Abs, [t]) if t.as_ref().is_none_or(is_numeric) => t.clone(),
+                    (Function::Lower | Function::Upper, [t])
+                        if t.as_ref().is_none_or(|t| *t == Type::Str) =>
+                    {
+                        Some(Type::Str)
+                    }
+                    (Function::Length, [t])
+                        if t.as_ref()
+                            .is_none_or(|t| matches!(t, Type::Str | Type::Bytes)) =>
+                    {
+                        Some(Type::Int)
+                    }
+                    (Function::Coalesce, [_, ..]) => {
+                        let mut known = types.iter().flatten();
+                        let first = known.next();
+                        if let Some(first) = first
+                            && known.any(|t| !comparable(first, t))
+                        {
+                            return Err(mismatch());
                         }
-                    },
-                };
+                        first.cloned()
+                    }
+                    _ => return Err(mismatch()),
+                }
+            }
+        };
 
-                derived.insert_rows(values);
+        Ok(atype)
+    }
 
-                return Some(derived);
+    /// Whether the expression can give NULL for rows of `schema`
+    pub fn nullable(&self, schema: &Schema) -> bool {
+        match self {
+            Expr::Attr(name) => schema
+                .index_of(name)
+                .is_ok_and(|i| schema.attributes[i].nullable),
+            Expr::Value(value) => *value == Value::Null,
+            Expr::Neg(expr) => expr.nullable(schema),
+            Expr::Compare(_, Comp::IsNull | Comp::IsNotNull, _) => false,
+            Expr::Binary(left, _, right) | Expr::Compare(left, _, right) => {
+                left.nullable(schema) || right.nullable(schema)
             }
-            _ => {}
-        }
-
-        let satisfied = self.iter().all(|a| relation.schema.attributes.contains(a));
-        if !satisfied {
-            println!(
-                "[ERROR][Projection] selected attributes {:?} dont exist",
-                &self
-            );
-            return None;
+            Expr::Call(Function::Coalesce, args) => args.iter().all(|arg| arg.nullable(schema)),
+            Expr::Call(_, args) => args.iter().any(|arg| arg.nullable(schema)),
         }
+    }
 
-        println!(
-            "[DEBUG][Projection] QUERY : SELECT attributes {:?}, returning tuples",
-            &self
-        );
-
-        let rel_attributes = self.iter().map(|f| f.clone()).collect::<Vec<_>>();
-
-        let selected_attrs_indices = self
-            .iter()
-            .map(|a| relation.schema.attributes.iter().position(|x| x == a))
-            .map(|i| i.unwrap())
-            .collect::<Vec<_>>();
-
-        let mut pk_missing = false;
-        if relation.pk.is_some() & !selected_attrs_indices.contains(&relation.pk.unwrap()) {
-            println!(
-                "[Projection] {:?} PK is not in the selected attributes",
-                &relation.pk
-            );
-            pk_missing = true;
-        }
+    /// The value of the expression for a row of `schema`
+    pub fn eval(&self, schema: &Schema, row: &impl Fields) -> Result<Value, Error> {
+        match self {
+            Expr::Attr(name) => Ok(row[schema.index_of(name)?].clone()),
+            Expr::Value(value) => Ok(value.clone()),
+            Expr::Neg(expr) => {
+                let overflow = || Error::Overflow(format!("{self:?}"));
+                match expr.eval(schema, row)? {
+                    Value::Int(i) => i.checked_neg().map(Value::Int).ok_or_else(overflow),
+                    Value::Float(f) => Ok(Value::Float(Float(-f.0))),
+                    Value::Decimal(d) => d
+                        .mantissa
+                        .checked_neg()
+                        .map(|m| Value::Decimal(Decimal::new(m, d.scale)))
+                        .ok_or_else(overflow),
+                    Value::Null => Ok(Value::Null),
+                    value => Err(Error::TypeMismatch(format!("-{value:?}"))),
+                }
+            }
+            Expr::Binary(left, op, right) => {
+                arithmetic(op, left.eval(schema, row)?, right.eval(schema, row)?)
+            }
+            Expr::Compare(left, comp, right) => {
+                let left = left.eval(schema, row)?;
+                if matches!(comp, Comp::IsNull | Comp::IsNotNull) {
+                    return Ok(comp.compare(&left, &Value::Null).into());
+                }
 
-        let values = relation
-            .data
-            .tuples()
-            .into_iter()
-            .map(|inner| {
-                inner
-                    .clone()
+                let (left, right) = promote(left, right.eval(schema, row)?);
+                Ok(comp.compare(&left, &right).into())
+            }
+            Expr::Call(function, args) => {
+                let args = args
                     .iter()
-                    .enumerate()
-                    .filter(|(index, _)| selected_attrs_indices.contains(index))
-                    .map(|(_, v)| v.clone())
-                    .collect::<Vec<_>>()
-            })
-            .collect::<std::collections::HashSet<Row>>() // remove the duplicates
-            .iter()
-            .map(|v| v.clone())
-            .collect::<Vec<_>>();
+                    .map(|arg| arg.eval(schema, row))
+                    .collect::<Result<Vec<_>, _>>()?;
+                call(function, args)
+            }
+        }
+    }
+}
 
-        if pk_missing {
-            let mut derived = Relation {
-                name: "derived".to_string(),
-                pk: None,
-                schema: Schema {
-                    attributes: rel_attributes,
-                },
-                data: Data::NoPK((0, Box::new(BTreeMap::new()))),
-            };
+impl From<Truth> for Value {
+    fn from(truth: Truth) -> Self {
+        match truth {
+            Truth::True => Value::Bool(true),
+            Truth::False => Value::Bool(false),
+            Truth::Unknown => Value::Null,
+        }
+    }
+}
 
-            derived.insert_rows(values);
-            return Some(derived);
+/// The type of a literal, none for NULL
+fn value_type(value: &Value) -> Option<Type> {
+    let atype = match value {
+        Value::Str(_) => Type::Str,
+        Value::Int(_) => Type::Int,
+        Value::Float(_) => Type::Float,
+        Value::Decimal(d) => {
+            let digits = d.mantissa.unsigned_abs().checked_ilog10().unwrap_or(0) + 1;
+            Type::Decimal(digits.max(d.scale), d.scale)
         }
+        Value::Bool(_) => Type::Bool,
+        Value::Date(_) => Type::Date,
+        Value::Timestamp(_) => Type::Timestamp,
+        Value::Bytes(_) => Type::Bytes,
+        Value::Null => return None,
+    };
 
-        let mut derived = Relation {
-            name: "derived".to_string(),
-            pk: relation.pk,
-            schema: Schema {
-                attributes: rel_attributes,
-            },
-            data: Data::WithPK(Box::new(BTreeMap::new())),
-        };
+    Some(atype)
+}
+
+/// Whether values of the two types are of the same kind, the same type or
+/// DECIMALs of any precision and scale
+fn same_kind(left: &Type, right: &Type) -> bool {
+    match (left, right) {
+        (Type::Decimal(..), Type::Decimal(..)) => true,
+        (l, r) => l == r,
+    }
+}
 
-        derived.insert_rows(values);
+fn is_numeric(atype: &Type) -> bool {
+    matches!(atype, Type::Int | Type::Float | Type::Decimal(..))
+}
 
-        return Some(derived);
+/// Values of the two types can be compared, numbers with each other
+fn comparable(left: &Type, right: &Type) -> bool {
+    match (left, right) {
+        (Type::Decimal(..), Type::Decimal(..)) => true,
+        _ => left == right || (is_numeric(left) && is_numeric(right)),
     }
 }
 
-impl<'a> Iterator for ProjAttrIterator<'a> {
-    type Item = &'a Attribute;
+/// The type of an arithmetic operation. INTs stay INTs, anything with a
+/// FLOAT is a FLOAT, and DECIMALs with INTs are DECIMALs.
+fn arithmetic_type(op: &BinOp, left: &Type, right: &Type) -> Option<Type> {
+    let scale = |t: &Type| match t {
+        Type::Decimal(_, scale) => *scale,
+        _ => 0,
+    };
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.current {
-            ProjAttrs::Attr(a, next) => {
-                if let Some(n) = next {
-                    self.current = n
-                } else {
-                    self.current = &ProjAttrs::None
-                }
-                Some(a)
-            }
-            ProjAttrs::None => None,
-        }
+    match (left, right) {
+        _ if *op == BinOp::Concat || !is_numeric(left) || !is_numeric(right) => None,
+        (Type::Int, Type::Int) => Some(Type::Int),
+        (Type::Float, _) | (_, Type::Float) => Some(Type::Float),
+        _ => Some(match op {
+            BinOp::Div => Type::Decimal(
+                Decimal::MAX_PRECISION,
+                scale(left).max(scale(right)).max(Decimal::DIVISION_SCALE),
+            ),
+            BinOp::Mul => Type::Decimal(Decimal::MAX_PRECISION, scale(left) + scale(right)),
+            _ => Type::Decimal(Decimal::MAX_PRECISION, scale(left).max(scale(right))),
+        }),
     }
 }
 
-// #[derive(Debug)]
-// pub type Predicates
+/// Brings two numbers to a common kind, INTs with DECIMALs to DECIMALs and
+/// anything with a FLOAT to FLOATs. Other values are left as they are.
+fn promote(left: Value, right: Value) -> (Value, Value) {
+    let float = |v: &Value| match v {
+        Value::Int(i) => Some(Value::Float(Float(*i as f64))),
+        Value::Float(f) => Some(Value::Float(*f)),
+        Value::Decimal(d) => Some(Value::Float(Float(d.to_f64()))),
+        _ => None,
+    };
+    let decimal = |v: &Value| match v {
+        Value::Int(i) => Some(Value::Decimal(Decimal::new(*i as i128, 0))),
+        Value::Decimal(d) => Some(Value::Decimal(*d)),
+        _ => None,
+    };
 
-// #[derive(Debug)]
-// pub struct Predicate {
-//     attribute: Attribute,
-// }
+    let kind = match (&left, &right) {
+        (Value::Float(_), _) | (_, Value::Float(_)) => float,
+        (Value::Decimal(_), _) | (_, Value::Decimal(_)) => decimal,
+        _ => return (left, right),
+    };
 
-#[derive(Debug)]
-pub enum UnaryOpr<'a> {
-    Selection(SelPredicate, &'a Relation),
-    Projection(ProjAttrs, &'a Relation),
+    match (kind(&left), kind(&right)) {
+        (Some(l), Some(r)) => (l, r),
+        _ => (left, right),
+    }
 }
 
-impl UnaryOpr<'_> {
-    pub fn evaluate(&self) -> Option<Relation> {
-        match self {
-            UnaryOpr::Projection(p, r) => {
-                return p.execute(*r);
-            }
-            UnaryOpr::Selection(_, _) => {
-                return None;
-            }
-        };
+fn arithmetic(op: &BinOp, left: Value, right: Value) -> Result<Value, Error> {
+    let overflow = Error::Overflow(format!("{left:?} {op:?} {right:?}"));
+    let mismatch = Error::TypeMismatch(format!("{left:?} {op:?} {right:?}"));
+
+    if left == Value::Null || right == Value::Null {
+        return Ok(Value::Null);
+    }
+    if matches!(op, BinOp::Div | BinOp::Mod)
+        && matches!(promote(right.clone(), Value::Float(Float(0.0))), (Value::Float(f), _) if f.0 == 0.0)
+    {
+        return Err(Error::DivisionByZero);
     }
-}
 
-#[derive(Debug)]
-pub enum BinaryOpr {}
+    let value = match (op, promote(left, right)) {
+        (BinOp::Concat, (Value::Str(l), Value::Str(r))) => Value::Str(l + &r),
+        (BinOp::Concat, _) => return Err(mismatch),
+        (op, (Value::Int(l), Value::Int(r))) => match op {
+            BinOp::Add => l.checked_add(r),
+            BinOp::Sub => l.checked_sub(r),
+            BinOp::Mul => l.checked_mul(r),
+            BinOp::Div => l.checked_div(r),
+            _ => l.checked_rem(r),
+        }
+        .map(Value::Int)
+        .ok_or(overflow)?,
+        (op, (Value::Float(l), Value::Float(r))) => Value::Float(Float(match op {
+            BinOp::Add => l.0 + r.0,
+            BinOp::Sub => l.0 - r.0,
+            BinOp::Mul => l.0 * r.0,
+            BinOp::Div => l.0 / r.0,
+            _ => l.0 % r.0,
+        })),
+        (op, (Value::Decimal(l), Value::Decimal(r))) => match op {
+            BinOp::Add => l.checked_add(r),
+            BinOp::Sub => l.checked_sub(r),
+            BinOp::Mul => l.checked_mul(r),
+            BinOp::Div => l.checked_div(r),
+            _ => l.checked_rem(r),
+        }
+        .map(Value::Decimal)
+        .ok_or(overflow)?,
+        _ => return Err(mismatch),
+    };
 
-#[derive(Debug)]
-pub enum Operator<'a> {
-    Unary(UnaryOpr<'a>),
-    Binary(BinaryOpr),
+    Ok(value)
 }
 
-impl Operator<'_> {
-    pub fn evaluate(&self) -> Option<Relation> {
-        match self {
-            Operator::Unary(opr) => {
-                return opr.evaluate();
-            }
-            Operator::Binary(_) => {
-                return None;
-            }
-        };
-    }
+fn call(function: &Function, args: Vec<Value>) -> Result<Value, Error> {
+    let mismatch = || Error::TypeMismatch(format!("{function:?}{args:?}"));
+
+    let value = match (function, &args[..]) {
+        (Function::Coalesce, _) => args
+            .iter()
+            .find(|v| **v != Value::Null)
+            .cloned()
+            .unwrap_or(Value::Null),
+        (_, [Value::Null]) => Value::Null,
+        (Function::Abs, [Value::Int(i)]) => Value::Int(
+            i.checked_abs()
+                .ok_or_else(|| Error::Overflow(format!("{function:?}({i})")))?,
+        ),
+        (Function::Abs, [Value::Float(f)]) => Value::Float(Float(f.0.abs())),
+        (Function::Abs, [Value::Decimal(d)]) => Value::Decimal(Decimal::new(
+            d.mantissa
+                .checked_abs()
+                .ok_or_else(|| Error::Overflow(format!("{function:?}({d})")))?,
+            d.scale,
+        )),
+        (Function::Lower, [Value::Str(s)]) => Value::Str(s.to_lowercase()),
+        (Function::Upper, [Value::Str(s)]) => Value::Str(s.to_uppercase()),
+        (Function::Length, [Value::Str(s)]) => Value::Int(s.chars().count() as i64),
+        (Function::Length, [Value::Bytes(b)]) => Value::Int(b.len() as i64),
+        _ => return Err(mismatch()),
+    };
+
+    Ok(value)
 }
 
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
+/// One condition of a [`SelPredicate`] chain
+enum Term<'a> {
+    Condition(&'a (Attribute, Comp, Operand)),
+    Expr(&'a Expr),
+    And(&'a SelPredicate, &'a SelPredicate),
+    Or(&'a SelPredicate, &'a SelPredicate),
+    Not(&'a SelPredicate),
 }
 
-#[cfg(test)]
-mod tests {
-    use std::vec;
+struct SelPredicateIterator<'a> {
+    current: &'a SelPredicate,
+    connective: Option<&'a Connective>,
+}
 
-    use super::*;
+impl SelPredicate {
+    /// Walks the chain yielding every condition along with the connective
+    /// linking it to the previous one (`None` for the first condition)
+    fn iter(&self) -> SelPredicateIterator<'_> {
+        SelPredicateIterator {
+            current: self,
+            connective: None,
+        }
+    }
 
-    fn create_test_schema() -> Schema {
-        let schema = Schema {
-            attributes: vec![
-                Attribute {
-                    name: "key".to_string(),
-                    atype: Type::Int,
-                },
-                Attribute {
-                    name: "value".to_string(),
-                    atype: Type::Str,
+    /// A predicate is valid for a schema when every condition refers to its
+    /// attributes and compares them against values or attributes of the
+    /// same type, or NULL
+    pub fn validate(&self, schema: &Schema) -> Result<(), Error> {
+        for (_, term) in self.iter() {
+            let (attr, comp, operand) = match term {
+                Term::Condition(cond) => cond,
+                Term::And(left, right) | Term::Or(left, right) => {
+                    left.validate(schema)?;
+                    right.validate(schema)?;
+                    continue;
+                }
+                Term::Not(predicate) => {
+                    predicate.validate(schema)?;
+                    continue;
+                }
+                Term::Expr(expr) => match expr.infer(schema)? {
+                    None | Some(Type::Bool) => continue,
+                    Some(atype) => {
+                        return Err(Error::TypeMismatch(format!(
+                            "{expr:?} is a {atype:?}, not a condition"
+                        )));
+                    }
                 },
-            ],
-        };
+            };
+            schema.position(attr)?;
+            let matches = match operand {
+                _ if matches!(comp, Comp::IsNull | Comp::IsNotNull) => true,
+                Operand::Value(value) => {
+                    value_type(value).is_none_or(|atype| same_kind(&attr.atype, &atype))
+                }
+                Operand::Attr(other) => {
+                    schema.position(other)?;
+                    same_kind(&attr.atype, &other.atype)
+                }
+            };
+            if !matches {
+                return Err(Error::TypeMismatch(format!(
+                    "{} {:?} compared against {:?}",
+                    attr.name, attr.atype, operand
+                )));
+            }
+        }
 
-        schema
+        Ok(())
     }
 
-    fn create_test_relation() -> Relation {
-        let schema = Schema {
-            attributes: vec![
-                Attribute {
-                    name: "key".to_string(),
-                    atype: Type::Int,
-                },
-                Attribute {
-                    name: "value".to_string(),
-                    atype: Type::Str,
-                },
-            ],
-        };
-
-        let relation = Relation {
-            name: "test".to_string(),
-            pk: Some(0),
-            // fks: None,
-            schema,
-            data: Data::WithPK(Box::new(BTreeMap::new())),
-        };
+    /// Pairs of attribute positions that are equal in every row the
+    /// predicate accepts. Only a pure conjunction guarantees any.
+    fn equalities(&self, schema: &Schema) -> Vec<(usize, usize)> {
+        if self
+            .iter()
+            .any(|(connective, _)| matches!(connective, Some(Connective::OR)))
+        {
+            return vec![];
+        }
 
-        relation
+        self.iter()
+            .flat_map(|(_, term)| match term {
+                Term::Condition((attr, Comp::EQ, Operand::Attr(other))) => {
+                    match (schema.position(attr), schema.position(other)) {
+                        (Ok(l), Ok(r)) => vec![(l, r)],
+                        _ => vec![],
+                    }
+                }
+                // values of different types can still compare equal, an INT
+                // and a FLOAT say, but are never equal as keys
+                Term::Expr(Expr::Compare(left, Comp::EQ, right)) => match (&**left, &**right) {
+                    (Expr::Attr(l), Expr::Attr(r)) => {
+                        match (schema.index_of(l), schema.index_of(r)) {
+                            (Ok(l), Ok(r))
+                                if same_kind(
+                                    &schema.attributes[l].atype,
+                                    &schema.attributes[r].atype,
+                                ) =>
+                            {
+                                vec![(l, r)]
+                            }
+                            _ => vec![],
+                        }
+                    }
+                    _ => vec![],
+                },
+                Term::And(left, right) => {
+                    let mut equalities = left.equalities(schema);
+                    equalities.extend(right.equalities(schema));
+                    equalities
+                }
+                _ => vec![],
+            })
+            .collect()
     }
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    /// Number of conditions in the predicate, counting those in its nodes
+    fn conditions(&self) -> usize {
+        self.iter()
+            .map(|(_, term)| match term {
+                Term::And(left, right) | Term::Or(left, right) => {
+                    left.conditions() + right.conditions()
+                }
+                Term::Not(predicate) => predicate.conditions(),
+                Term::Condition(_) | Term::Expr(_) => 1,
+            })
+            .sum()
     }
 
-    #[test]
-    fn validate_invalid_row_schema() {
-        let schema = create_test_schema();
-
-        assert_eq!(
-            schema.validate_row(&vec![Value::Str("foo".to_string()), Value::Int(1)]),
-            false
-        )
+    /// Whether the predicate accepts a row of `schema`, which it only does
+    /// when it is true for the row, not when it is unknown. An error while
+    /// evaluating it is returned rather than taken as a rejection.
+    pub fn evaluate(&self, schema: &Schema, row: &impl Fields) -> Result<bool, Error> {
+        Ok(matches!(self.truth(schema, row)?, Truth::True))
     }
 
-    #[test]
+    /// Evaluates the predicate against a row of `schema`. Like SQL, AND
+    /// binds tighter than OR in a chain, so `a OR b AND c` reads as
+    /// `a OR (b AND c)`, and nodes evaluate on their own before the chain
+    /// uses them. `SelPredicate::None` is true for every row.
+    pub fn truth(&self, schema: &Schema, row: &impl Fields) -> Result<Truth, Error> {
+        let mut any = Truth::False;
+        let mut all = Truth::True;
+
+        for (connective, term) in self.iter() {
+            let satisfied = match term {
+                Term::Condition((attr, comp, Operand::Value(value))) => {
+                    comp.compare(&row[schema.position(attr)?], value)
+                }
+                Term::Condition((attr, comp, Operand::Attr(other))) => {
+                    comp.compare(&row[schema.position(attr)?], &row[schema.position(other)?])
+                }
+                Term::And(left, right) => left.truth(schema, row)?.and(right.truth(schema, row)?),
+                Term::Or(left, right) => left.truth(schema, row)?.or(right.truth(schema, row)?),
+                Term::Not(predicate) => !predicate.truth(schema, row)?,
+                Term::Expr(expr) => match expr.eval(schema, row)? {
+                    Value::Bool(b) => b.into(),
+                    Value::Null => Truth::Unknown,
+                    value => {
+                        return Err(Error::TypeMismatch(format!(
+                            "{value:?} from {expr:?} is not a condition"
+                        )));
+                    }
+                },
+            };
+
+            match connective {
+                Some(Connective::OR) => {
+                    any = any.or(all);
+                    all = satisfied;
+                }
+                Some(Connective::AND) | None => all = all.and(satisfied),
+            }
+        }
+
+        Ok(any.or(all))
+    }
+
+    pub fn execute(&self, relation: &Relation) -> Result<Relation, Error> {
+        self.validate(&relation.schema)?;
+
+        let mut values = vec![];
+        for row in relation.data.tuples() {
+            if self.truth(&relation.schema, &row)? == Truth::True {
+                values.push(row);
+            }
+        }
+
+        let mut derived =
+            Relation::derived_from(relation, relation.pk.clone(), relation.schema.clone());
+        derived.insert_rows(values)?;
+
+        Ok(derived)
+    }
+}
+
+impl<'a> Iterator for SelPredicateIterator<'a> {
+    type Item = (Option<&'a Connective>, Term<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (term, next) = match self.current {
+            SelPredicate::Condition(cond, next) => (Term::Condition(cond), next),
+            SelPredicate::Expr(expr, next) => (Term::Expr(expr), next),
+            SelPredicate::And(left, right) => (Term::And(left, right), &None),
+            SelPredicate::Or(left, right) => (Term::Or(left, right), &None),
+            SelPredicate::Not(predicate) => (Term::Not(predicate), &None),
+            SelPredicate::None => return None,
+        };
+
+        let connective = self.connective;
+        if let Some((c, n)) = next {
+            self.connective = Some(c);
+            self.current = n;
+        } else {
+            self.current = &SelPredicate::None;
+        }
+        Some((connective, term))
+    }
+}
+
+/// The columns a projection keeps, linked like [`SelPredicate`]
+#[derive(Debug)]
+pub enum ProjAttrs {
+    /// An attribute of the relation, matched by name. It keeps the name it
+    /// is given here and the type it has in the relation.
+    Attr(Attribute, Option<Box<ProjAttrs>>),
+    /// A column computed by an expression, named by the alias, with the
+    /// type the expression gives
+    Expr(Expr, String, Option<Box<ProjAttrs>>),
+    None,
+}
+
+/// One column of a [`ProjAttrs`] chain
+enum Column<'a> {
+    Attr(&'a Attribute),
+    Expr(&'a Expr, &'a str),
+}
+
+struct ProjAttrIterator<'a> {
+    current: &'a ProjAttrs,
+}
+
+impl ProjAttrs {
+    fn iter(&self) -> ProjAttrIterator<'_> {
+        ProjAttrIterator { current: self }
+    }
+
+    pub fn execute(&self, relation: &Relation) -> Result<Relation, Error> {
+        if let ProjAttrs::None = self {
+            // Same as SELECT * FROM relation
+            let values = distinct(relation.data.tuples());
+
+            let mut derived =
+                Relation::derived_from(relation, relation.pk.clone(), relation.schema.clone());
+
+            derived.insert_rows(values)?;
+
+            return Ok(derived);
+        }
+
+        let schema = &relation.schema;
+        let mut exprs = vec![];
+        let mut rel_attributes = vec![];
+        for column in self.iter() {
+            let (expr, attribute) = match column {
+                Column::Attr(a) => {
+                    let attr = &schema.attributes[schema.index_of(&a.name)?];
+                    let attribute = Attribute {
+                        name: a.name.clone(),
+                        ..attr.clone()
+                    };
+                    (Cow::Owned(Expr::Attr(attr.name.clone())), attribute)
+                }
+                Column::Expr(expr, alias) => {
+                    let attribute = Attribute {
+                        name: alias.to_string(),
+                        atype: expr.infer_type(schema)?,
+                        nullable: expr.nullable(schema),
+                    };
+                    (Cow::Borrowed(expr), attribute)
+                }
+            };
+
+            if rel_attributes
+                .iter()
+                .any(|a: &Attribute| a.name == attribute.name)
+            {
+                return Err(Error::DuplicateAttribute(attribute.name));
+            }
+            exprs.push(expr);
+            rel_attributes.push(attribute);
+        }
+
+        // the key carries over when all of its attributes are selected, at
+        // their new positions
+        let selected_attrs_indices = exprs
+            .iter()
+            .map(|expr| match expr.as_ref() {
+                Expr::Attr(name) => schema.index_of(name).ok(),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let pk = relation.pk.as_ref().and_then(|pk| {
+            pk.iter()
+                .map(|i| selected_attrs_indices.iter().position(|s| *s == Some(*i)))
+                .collect::<Option<Vec<_>>>()
+        });
+
+        let mut values = vec![];
+        for row in relation.data.tuples() {
+            values.push(
+                exprs
+                    .iter()
+                    .map(|expr| expr.eval(schema, &row))
+                    .collect::<Result<Row, _>>()?,
+            );
+        }
+        // remove the duplicates, keeping the order of the tuples
+        let values = distinct(values);
+
+        let mut derived = Relation::derived_from(
+            relation,
+            pk,
+            Schema {
+                attributes: rel_attributes,
+            },
+        );
+
+        derived.insert_rows(values)?;
+
+        Ok(derived)
+    }
+}
+
+impl<'a> Iterator for ProjAttrIterator<'a> {
+    type Item = Column<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (column, next) = match self.current {
+            ProjAttrs::Attr(a, next) => (Column::Attr(a), next),
+            ProjAttrs::Expr(expr, alias, next) => (Column::Expr(expr, alias), next),
+            ProjAttrs::None => return None,
+        };
+
+        if let Some(n) = next {
+            self.current = n
+        } else {
+            self.current = &ProjAttrs::None
+        }
+        Some(column)
+    }
+}
+
+// #[derive(Debug)]
+// pub type Predicates
+
+// #[derive(Debug)]
+// pub struct Predicate {
+//     attribute: Attribute,
+// }
+
+#[derive(Debug)]
+pub enum UnaryOpr<'a> {
+    Selection(SelPredicate, Input<'a>),
+    Projection(ProjAttrs, Input<'a>),
+    /// ρ, gives the relation a new name when there is one, and renames
+    /// attributes from the first name of each pair to the second
+    Rename(Option<String>, Vec<(String, String)>, Input<'a>),
+    /// γ, groups the tuples on the named attributes, which become the key
+    /// of the result, and computes the aggregates for every group
+    Aggregation(Vec<String>, Vec<Aggregate>, Input<'a>),
+    /// τ, orders the tuples on the keys, the first key first
+    Sort(Vec<SortKey>, Input<'a>),
+    /// The first tuples, at most as many as given
+    Limit(usize, Input<'a>),
+    /// The tuples after skipping as many as given
+    Offset(usize, Input<'a>),
+}
+
+impl UnaryOpr<'_> {
+    pub fn evaluate(&self) -> Result<Relation, Error> {
+        let r = self.input().resolve()?;
+        let r = r.as_ref();
+
+        match self {
+            UnaryOpr::Projection(p, _) => p.execute(r),
+            UnaryOpr::Selection(p, _) => p.execute(r),
+            UnaryOpr::Rename(name, attrs, _) => rename(name.as_deref(), attrs, r),
+            UnaryOpr::Aggregation(group_by, aggs, _) => aggregate(group_by, aggs, r),
+            UnaryOpr::Sort(keys, _) => sort(keys, r),
+            UnaryOpr::Limit(count, _) => slice(r, 0, Some(*count)),
+            UnaryOpr::Offset(count, _) => slice(r, *count, None),
+        }
+    }
+
+    fn input(&self) -> &Input<'_> {
+        match self {
+            UnaryOpr::Selection(_, r)
+            | UnaryOpr::Projection(_, r)
+            | UnaryOpr::Rename(_, _, r)
+            | UnaryOpr::Aggregation(_, _, r)
+            | UnaryOpr::Sort(_, r)
+            | UnaryOpr::Limit(_, r)
+            | UnaryOpr::Offset(_, r) => r,
+        }
+    }
+}
+
+/// An aggregate function over the named attribute. NULLs are skipped, and
+/// SUM, MIN, MAX and AVG give NULL when there is nothing left.
+#[derive(Debug)]
+pub enum Aggregate {
+    /// Number of tuples, or with an attribute the number of non-NULL values
+    Count(Option<String>),
+    /// Sum of an INT, FLOAT or DECIMAL attribute, of the same type with
+    /// the largest precision for a DECIMAL
+    Sum(String),
+    Min(String),
+    Max(String),
+    /// Average of an INT, FLOAT or DECIMAL attribute, as a FLOAT for INTs
+    /// and FLOATs and as a DECIMAL divided like [`BinOp::Div`] otherwise
+    Avg(String),
+}
+
+impl Aggregate {
+    fn attribute(&self) -> Option<&str> {
+        match self {
+            Aggregate::Count(attr) => attr.as_deref(),
+            Aggregate::Sum(attr)
+            | Aggregate::Min(attr)
+            | Aggregate::Max(attr)
+            | Aggregate::Avg(attr) => Some(attr),
+        }
+    }
+
+    fn function(&self) -> &'static str {
+        match self {
+            Aggregate::Count(_) => "count",
+            Aggregate::Sum(_) => "sum",
+            Aggregate::Min(_) => "min",
+            Aggregate::Max(_) => "max",
+            Aggregate::Avg(_) => "avg",
+        }
+    }
+
+    /// The name of the attribute holding the aggregate in the result, such
+    /// as `sum(price)` with the attribute named as it is here, or `count`
+    /// for a count of tuples
+    pub(crate) fn name(&self) -> String {
+        match self.attribute() {
+            Some(name) => format!("{}({name})", self.function()),
+            None => self.function().to_string(),
+        }
+    }
+
+    fn output(&self, schema: &Schema) -> Result<Attribute, Error> {
+        let function = self.function();
+
+        let attr = match self.attribute() {
+            Some(name) => &schema.attributes[schema.index_of(name)?],
+            None => {
+                return Ok(Attribute {
+                    name: self.name(),
+                    atype: Type::Int,
+                    nullable: false,
+                });
+            }
+        };
+
+        let atype = match self {
+            Aggregate::Count(_) => Type::Int,
+            Aggregate::Min(_) | Aggregate::Max(_) => attr.atype.clone(),
+            Aggregate::Sum(_) | Aggregate::Avg(_) => match attr.atype {
+                Type::Int | Type::Float if matches!(self, Aggregate::Avg(_)) => Type::Float,
+                Type::Decimal(_, scale) if matches!(self, Aggregate::Avg(_)) => {
+                    Type::Decimal(Decimal::MAX_PRECISION, scale.max(Decimal::DIVISION_SCALE))
+                }
+                Type::Int => Type::Int,
+                Type::Float => Type::Float,
+                Type::Decimal(_, scale) => Type::Decimal(Decimal::MAX_PRECISION, scale),
+                _ => {
+                    return Err(Error::TypeMismatch(format!(
+                        "{function} over {} {:?}",
+                        attr.name, attr.atype
+                    )));
+                }
+            },
+        };
+
+        Ok(Attribute {
+            name: self.name(),
+            atype,
+            nullable: !matches!(self, Aggregate::Count(_)),
+        })
+    }
+
+    /// Computes the aggregate over the values of one group
+    fn compute(&self, values: Vec<&Value>) -> Result<Value, Error> {
+        let values = values
+            .into_iter()
+            .filter(|v| self.attribute().is_none() || **v != Value::Null)
+            .collect::<Vec<_>>();
+
+        let sum = || {
+            values
+                .iter()
+                .try_fold(Value::Int(0), |acc, v| match (acc, v) {
+                    (Value::Int(acc), Value::Int(i)) => acc
+                        .checked_add(*i)
+                        .map(Value::Int)
+                        .ok_or_else(|| Error::Overflow(format!("{:?} does not fit an INT", self))),
+                    (Value::Int(acc), Value::Float(f)) => Ok(Value::Float(Float(acc as f64 + f.0))),
+                    (Value::Float(acc), Value::Float(f)) => Ok(Value::Float(Float(acc.0 + f.0))),
+                    (Value::Int(acc), Value::Decimal(d)) => Decimal::new(acc as i128, 0)
+                        .checked_add(*d)
+                        .map(Value::Decimal)
+                        .ok_or_else(|| {
+                            Error::Overflow(format!("{:?} does not fit a DECIMAL", self))
+                        }),
+                    (Value::Decimal(acc), Value::Decimal(d)) => {
+                        acc.checked_add(*d).map(Value::Decimal).ok_or_else(|| {
+                            Error::Overflow(format!("{:?} does not fit a DECIMAL", self))
+                        })
+                    }
+                    (acc, _) => Ok(acc),
+                })
+        };
+
+        let value = match self {
+            Aggregate::Count(_) => Value::Int(values.len() as i64),
+            _ if values.is_empty() => Value::Null,
+            Aggregate::Sum(_) => sum()?,
+            Aggregate::Avg(_) => match sum()? {
+                Value::Int(sum) => Value::Float(Float(sum as f64 / values.len() as f64)),
+                Value::Float(sum) => Value::Float(Float(sum.0 / values.len() as f64)),
+                Value::Decimal(sum) => sum
+                    .checked_div(Decimal::new(values.len() as i128, 0))
+                    .map(Value::Decimal)
+                    .ok_or_else(|| Error::Overflow(format!("{:?} does not fit a DECIMAL", self)))?,
+                sum => sum,
+            },
+            Aggregate::Min(_) => values.into_iter().min().unwrap().clone(),
+            Aggregate::Max(_) => values.into_iter().max().unwrap().clone(),
+        };
+
+        Ok(value)
+    }
+}
+
+fn aggregate(
+    group_by: &[String],
+    aggregates: &[Aggregate],
+    relation: &Relation,
+) -> Result<Relation, Error> {
+    let schema = &relation.schema;
+    let grouped = group_by
+        .iter()
+        .map(|name| schema.index_of(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let inputs = aggregates
+        .iter()
+        .map(|agg| {
+            agg.attribute()
+                .map(|name| schema.index_of(name))
+                .transpose()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut attributes = grouped
+        .iter()
+        .map(|i| schema.attributes[*i].clone())
+        .collect::<Vec<_>>();
+    for agg in aggregates {
+        attributes.push(agg.output(schema)?);
+    }
+
+    let mut names = HashSet::new();
+    if let Some(a) = attributes.iter().find(|a| !names.insert(&a.name)) {
+        return Err(Error::DuplicateAttribute(a.name.clone()));
+    }
+
+    let tuples = relation.data.tuples();
+    let mut groups: Vec<Row> = vec![];
+    let mut members: HashMap<Row, Vec<&Row>> = HashMap::new();
+    for row in &tuples {
+        let group = grouped.iter().map(|i| row[*i].clone()).collect::<Row>();
+        if !members.contains_key(&group) {
+            groups.push(group.clone());
+        }
+        members.entry(group).or_default().push(row);
+    }
+
+    // without grouping there is always a single group, even with no tuples
+    if grouped.is_empty() && groups.is_empty() {
+        groups.push(vec![]);
+        members.insert(vec![], vec![]);
+    }
+
+    let mut values = vec![];
+    for group in groups {
+        let rows = &members[&group];
+        let mut row = group;
+        for (agg, input) in aggregates.iter().zip(&inputs) {
+            let column = rows
+                .iter()
+                .map(|r| input.map_or(&Value::Null, |i| &r[i]))
+                .collect();
+            row.push(agg.compute(column)?);
+        }
+        values.push(row);
+    }
+
+    // a NULL cannot be part of a key
+    let pk = match grouped.is_empty() || attributes[..grouped.len()].iter().any(|a| a.nullable) {
+        true => None,
+        false => Some((0..grouped.len()).collect()),
+    };
+
+    let mut derived = Relation::derived(pk, Schema { attributes });
+    derived.insert_rows(values)?;
+
+    Ok(derived)
+}
+
+/// An attribute to sort on, by name. NULLs come last in ascending order and
+/// first in descending order.
+#[derive(Debug)]
+pub enum SortKey {
+    Asc(String),
+    Desc(String),
+}
+
+fn sort(keys: &[SortKey], relation: &Relation) -> Result<Relation, Error> {
+    let keys = keys
+        .iter()
+        .map(|key| match key {
+            SortKey::Asc(name) => relation.schema.index_of(name).map(|i| (i, false)),
+            SortKey::Desc(name) => relation.schema.index_of(name).map(|i| (i, true)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // a stable sort, tuples equal on every key keep their order
+    let mut values = relation.data.tuples();
+    values.sort_by(|a, b| {
+        keys.iter()
+            .map(|(i, desc)| match desc {
+                true => b[*i].cmp(&a[*i]),
+                false => a[*i].cmp(&b[*i]),
+            })
+            .find(|ord| ord.is_ne())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut derived =
+        Relation::derived_from(relation, relation.pk.clone(), relation.schema.clone());
+    derived.data = Data::Ordered(Box::default(), HashSet::new());
+    derived.insert_rows(values)?;
+
+    Ok(derived)
+}
+
+/// The tuples from `offset` onwards, at most `limit` of them, in order
+fn slice(relation: &Relation, offset: usize, limit: Option<usize>) -> Result<Relation, Error> {
+    let values = relation
+        .data
+        .tuples()
+        .into_iter()
+        .skip(offset)
+        .take(limit.unwrap_or(usize::MAX))
+        .collect::<Vec<_>>();
+
+    let mut derived =
+        Relation::derived_from(relation, relation.pk.clone(), relation.schema.clone());
+    derived.data = Data::Ordered(Box::default(), HashSet::new());
+    derived.insert_rows(values)?;
+
+    Ok(derived)
+}
+
+fn rename(
+    name: Option<&str>,
+    attrs: &[(String, String)],
+    relation: &Relation,
+) -> Result<Relation, Error> {
+    let mut schema = relation.schema.clone();
+    let mut renamed = HashSet::new();
+
+    for (from, to) in attrs {
+        // resolved against the original names, so `a -> b, b -> a` swaps
+        let index = relation.schema.index_of(from)?;
+        if !renamed.insert(index) {
+            return Err(Error::DuplicateAttribute(from.clone()));
+        }
+        schema.attributes[index].name = to.clone();
+    }
+
+    let mut names = HashSet::new();
+    if let Some(a) = schema.attributes.iter().find(|a| !names.insert(&a.name)) {
+        return Err(Error::DuplicateAttribute(a.name.clone()));
+    }
+
+    let mut derived = Relation::derived_from(relation, relation.pk.clone(), schema);
+    derived.name = name.unwrap_or(&relation.name).to_string();
+    derived.insert_rows(relation.data.tuples())?;
+
+    Ok(derived)
+}
+
+#[derive(Debug)]
+pub enum BinaryOpr<'a> {
+    /// Tuples present in either relation, without duplicates
+    Union(Input<'a>, Input<'a>),
+    /// Tuples of the left relation that are not in the right one
+    Difference(Input<'a>, Input<'a>),
+    /// Tuples present in both relations
+    Intersection(Input<'a>, Input<'a>),
+    /// Every tuple of the left relation combined with every tuple of the
+    /// right one. Attributes are qualified with their relation's name.
+    Product(Input<'a>, Input<'a>),
+    /// Pairs of tuples that agree on every attribute the two relations
+    /// share by name and type, with the shared attributes kept once
+    NaturalJoin(Input<'a>, Input<'a>),
+    /// Pairs of tuples of the product that satisfy the predicate
+    ThetaJoin(SelPredicate, Input<'a>, Input<'a>),
+    /// The theta join, plus the left tuples that match nothing padded with
+    /// NULLs for the right attributes
+    LeftOuterJoin(SelPredicate, Input<'a>, Input<'a>),
+    /// The theta join, plus the right tuples that match nothing padded with
+    /// NULLs for the left attributes
+    RightOuterJoin(SelPredicate, Input<'a>, Input<'a>),
+    /// The theta join, plus the unmatched tuples of both sides
+    FullOuterJoin(SelPredicate, Input<'a>, Input<'a>),
+    /// ⋉, tuples of the left relation with a match in the right one
+    SemiJoin(SelPredicate, Input<'a>, Input<'a>),
+    /// ▷, tuples of the left relation without a match in the right one
+    AntiJoin(SelPredicate, Input<'a>, Input<'a>),
+    /// ÷, the tuples over the dividend's remaining attributes that appear in
+    /// the dividend together with every tuple of the divisor
+    Division(Input<'a>, Input<'a>),
+}
+
+impl BinaryOpr<'_> {
+    pub fn evaluate(&self) -> Result<Relation, Error> {
+        let (left, right) = self.inputs();
+        let left = left.resolve()?;
+        let right = right.resolve()?;
+        let (left, right) = (left.as_ref(), right.as_ref());
+
+        match self {
+            BinaryOpr::Union(..) => {
+                left.schema.union_compatible(&right.schema)?;
+
+                let values = distinct(left.data.tuples().into_iter().chain(right.data.tuples()));
+
+                // both sides may use the same key for different tuples, in
+                // which case the key no longer identifies a tuple
+                let pk = left.pk.clone().filter(|pk| {
+                    right.pk.as_ref() == Some(pk)
+                        && values
+                            .iter()
+                            .map(|row| pk.iter().map(|i| &row[*i]).collect::<Vec<_>>())
+                            .collect::<HashSet<_>>()
+                            .len()
+                            == values.len()
+                });
+
+                let mut derived = Relation::derived(pk, left.schema.union(&right.schema));
+                derived.insert_rows(values)?;
+
+                Ok(derived)
+            }
+            BinaryOpr::Difference(..) => {
+                left.schema.union_compatible(&right.schema)?;
+
+                let exclude = right.data.tuples().into_iter().collect::<HashSet<_>>();
+                let values = distinct(
+                    left.data
+                        .tuples()
+                        .into_iter()
+                        .filter(|row| !exclude.contains(row)),
+                );
+
+                // a subset of the left tuples, so its key still holds
+                let mut derived = Relation::derived(left.pk.clone(), left.schema.clone());
+                derived.insert_rows(values)?;
+
+                Ok(derived)
+            }
+            BinaryOpr::Intersection(..) => {
+                left.schema.union_compatible(&right.schema)?;
+
+                let include = right.data.tuples().into_iter().collect::<HashSet<_>>();
+                let values = distinct(
+                    left.data
+                        .tuples()
+                        .into_iter()
+                        .filter(|row| include.contains(row)),
+                );
+
+                // a subset of the tuples on either side, so either key holds
+                let mut derived =
+                    Relation::derived(left.pk.clone().or(right.pk.clone()), left.schema.clone());
+                derived.insert_rows(values)?;
+
+                Ok(derived)
+            }
+            BinaryOpr::Product(..) => join(&SelPredicate::None, left, right, false, false),
+            BinaryOpr::NaturalJoin(..) => natural_join(left, right),
+            BinaryOpr::ThetaJoin(p, ..) => join(p, left, right, false, false),
+            BinaryOpr::LeftOuterJoin(p, ..) => join(p, left, right, true, false),
+            BinaryOpr::RightOuterJoin(p, ..) => join(p, left, right, false, true),
+            BinaryOpr::FullOuterJoin(p, ..) => join(p, left, right, true, true),
+            BinaryOpr::SemiJoin(p, ..) => semi_join(p, left, right, false),
+            BinaryOpr::AntiJoin(p, ..) => semi_join(p, left, right, true),
+            BinaryOpr::Division(..) => division(left, right),
+        }
+    }
+
+    fn inputs(&self) -> (&Input<'_>, &Input<'_>) {
+        match self {
+            BinaryOpr::Union(left, right)
+            | BinaryOpr::Difference(left, right)
+            | BinaryOpr::Intersection(left, right)
+            | BinaryOpr::Product(left, right)
+            | BinaryOpr::NaturalJoin(left, right)
+            | BinaryOpr::Division(left, right)
+            | BinaryOpr::ThetaJoin(_, left, right)
+            | BinaryOpr::LeftOuterJoin(_, left, right)
+            | BinaryOpr::RightOuterJoin(_, left, right)
+            | BinaryOpr::FullOuterJoin(_, left, right)
+            | BinaryOpr::SemiJoin(_, left, right)
+            | BinaryOpr::AntiJoin(_, left, right) => (left, right),
+        }
+    }
+}
+
+/// Pairs of tuples that satisfy `predicate`. Unmatched tuples of the left
+/// side are kept when `keep_left` is set and those of the right side when
+/// `keep_right` is, padded with NULLs on the other side, which gives the
+/// left, right and full outer joins.
+fn join(
+    predicate: &SelPredicate,
+    left: &Relation,
+    right: &Relation,
+    keep_left: bool,
+    keep_right: bool,
+) -> Result<Relation, Error> {
+    let mut schema = joined_schema(left, right)?;
+    predicate.validate(&schema)?;
+
+    // equalities across the two sides are all that says anything about the
+    // key
+    let split = left.schema.attributes.len();
+    let matched = cross_equalities(predicate, &schema, split);
+    let pk = match join_key(left, right, &matched, |j| split + j) {
+        // padding puts NULLs in the attributes of the side that is not kept
+        Some(pk) if keep_left && pk.iter().any(|i| *i >= split) => None,
+        Some(pk) if keep_right && pk.iter().any(|i| *i < split) => None,
+        pk => pk,
+    };
+
+    let left_tuples = left.data.tuples();
+    let right_tuples = right.data.tuples();
+    let pairs = join_pairs(predicate, &schema, &matched, &left_tuples, &right_tuples)?;
+    let concat = |l: &Row, r: &Row| l.iter().chain(r).cloned().collect::<Row>();
+
+    let left_nulls = vec![Value::Null; split];
+    let right_nulls = vec![Value::Null; right.schema.attributes.len()];
+    let mut right_matched = vec![false; right_tuples.len()];
+    let mut values = vec![];
+    let mut pairs = pairs.into_iter().peekable();
+
+    for (i, l) in left_tuples.iter().enumerate() {
+        let mut found = false;
+        while let Some((_, j)) = pairs.next_if(|(p, _)| *p == i) {
+            values.push(concat(l, &right_tuples[j]));
+            right_matched[j] = true;
+            found = true;
+        }
+        if keep_left && !found {
+            values.push(concat(l, &right_nulls));
+        }
+    }
+    if keep_right {
+        for (r, _) in right_tuples.iter().zip(right_matched).filter(|(_, m)| !m) {
+            values.push(concat(&left_nulls, r));
+        }
+    }
+
+    for (i, a) in schema.attributes.iter_mut().enumerate() {
+        a.nullable |= match i < split {
+            true => keep_right,
+            false => keep_left,
+        };
+    }
+
+    let mut derived = Relation::derived(pk, schema);
+    derived.insert_rows(values)?;
+
+    Ok(derived)
+}
+
+/// Tuples of the left relation that match some tuple of the right one under
+/// `predicate`, or with `anti` set, those that match none
+fn semi_join(
+    predicate: &SelPredicate,
+    left: &Relation,
+    right: &Relation,
+    anti: bool,
+) -> Result<Relation, Error> {
+    let schema = joined_schema(left, right)?;
+    predicate.validate(&schema)?;
+
+    let matched = cross_equalities(predicate, &schema, left.schema.attributes.len());
+    let residual = matched.is_empty() || predicate.conditions() > matched.len();
+    let right_tuples = right.data.tuples();
+
+    // whether `l` matches any of `candidates`, stopping at the first match
+    let any = |l: &Row, candidates: &mut dyn Iterator<Item = &Row>| {
+        candidates
+            .map(|r| {
+                predicate
+                    .truth(&schema, &Joined(l, r))
+                    .map(|truth| truth == Truth::True)
+            })
+            .find(|found| !matches!(found, Ok(false)))
+            .unwrap_or(Ok(false))
+    };
+
+    // with equalities across the sides, only the right tuples with the
+    // same join attributes are candidates
+    let mut table: HashMap<Key, Vec<&Row>> = HashMap::new();
+    for r in right_tuples.iter().filter(|_| !matched.is_empty()) {
+        let key = matched.iter().map(|(_, j)| r[*j].clone()).collect::<Key>();
+        if !key.contains(&Value::Null) {
+            table.entry(key).or_default().push(r);
+        }
+    }
+
+    let mut values = vec![];
+    for l in left.data.tuples() {
+        let found = match matched.is_empty() {
+            true => any(&l, &mut right_tuples.iter())?,
+            false => {
+                let key = matched.iter().map(|(i, _)| l[*i].clone()).collect::<Key>();
+                match table.get(&key) {
+                    Some(candidates) if residual => any(&l, &mut candidates.iter().copied())?,
+                    Some(_) => true,
+                    None => false,
+                }
+            }
+        };
+        if found != anti {
+            values.push(l);
+        }
+    }
+
+    let mut derived = Relation::derived_from(left, left.pk.clone(), left.schema.clone());
+    derived.insert_rows(values)?;
+
+    Ok(derived)
+}
+
+/// Schema of the pairs of tuples a join looks at, the attributes of both
+/// relations qualified with the relation's name. Relations with the same
+/// name, as in a product of a relation with itself, need a rename first.
+fn joined_schema(left: &Relation, right: &Relation) -> Result<Schema, Error> {
+    let attributes = left
+        .schema
+        .attributes
+        .iter()
+        .map(|a| a.qualified(&left.name))
+        .chain(
+            right
+                .schema
+                .attributes
+                .iter()
+                .map(|a| a.qualified(&right.name)),
+        )
+        .collect();
+
+    join_schema(attributes)
+}
+
+/// The schema of a join, whose attributes must have different names even
+/// once qualified, which they do not when a relation is joined with itself
+fn join_schema(attributes: Vec<Attribute>) -> Result<Schema, Error> {
+    Schema::new(attributes).map_err(|e| match e {
+        Error::DuplicateAttribute(name) => Error::DuplicateAttribute(format!(
+            "{name}, rename one of the relations to tell them apart"
+        )),
+        e => e,
+    })
+}
+
+/// The equalities of `predicate` between an attribute of the left side and
+/// one of the right side, the latter at its position in the right relation.
+/// `split` is the number of attributes of the left side.
+fn cross_equalities(
+    predicate: &SelPredicate,
+    schema: &Schema,
+    split: usize,
+) -> Vec<(usize, usize)> {
+    predicate
+        .equalities(schema)
+        .into_iter()
+        .filter_map(|(a, b)| match (a < split, b < split) {
+            (true, false) => Some((a, b - split)),
+            (false, true) => Some((b, a - split)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Indices of the pairs of tuples that satisfy `predicate`. With equalities
+/// across the sides in `matched` the pairs are found with a hash join,
+/// otherwise every pair of the product is checked.
+fn join_pairs(
+    predicate: &SelPredicate,
+    schema: &Schema,
+    matched: &[(usize, usize)],
+    left_tuples: &[Row],
+    right_tuples: &[Row],
+) -> Result<Vec<(usize, usize)>, Error> {
+    let satisfies = |i: usize, j: usize| {
+        predicate
+            .truth(schema, &Joined(&left_tuples[i], &right_tuples[j]))
+            .map(|truth| truth == Truth::True)
+    };
+
+    let candidates: Box<dyn Iterator<Item = (usize, usize)>> = match matched.is_empty() {
+        true => Box::new(
+            (0..left_tuples.len()).flat_map(|i| (0..right_tuples.len()).map(move |j| (i, j))),
+        ),
+        false => Box::new(hash_join(left_tuples, right_tuples, matched).into_iter()),
+    };
+
+    // conditions other than the equalities are checked on the pairs
+    let residual = matched.is_empty() || predicate.conditions() > matched.len();
+    let mut pairs = vec![];
+    for (i, j) in candidates {
+        if !residual || satisfies(i, j)? {
+            pairs.push((i, j));
+        }
+    }
+
+    Ok(pairs)
+}
+
+fn natural_join(left: &Relation, right: &Relation) -> Result<Relation, Error> {
+    let left_attrs = &left.schema.attributes;
+    let right_attrs = &right.schema.attributes;
+
+    let shared = left_attrs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, a)| {
+            right_attrs
+                .iter()
+                .position(|b| b.name == a.name && same_kind(&b.atype, &a.atype))
+                .map(|j| (i, j))
+        })
+        .collect::<Vec<_>>();
+    let rest = (0..right_attrs.len())
+        .filter(|j| !shared.iter().any(|(_, s)| s == j))
+        .collect::<Vec<_>>();
+
+    // a name on both sides with different types is not joined on, and
+    // needs qualifying to tell the two apart
+    let clashes = |a: &Attribute| rest.iter().any(|j| right_attrs[*j].name == a.name);
+    let schema = join_schema(
+        left_attrs
+            .iter()
+            .map(|a| match clashes(a) {
+                true => a.qualified(&left.name),
+                false => a.clone(),
+            })
+            .chain(rest.iter().map(|j| {
+                let b = &right_attrs[*j];
+                match left_attrs.iter().any(|a| a.name == b.name) {
+                    true => b.qualified(&right.name),
+                    false => b.clone(),
+                }
+            }))
+            .collect(),
+    )?;
+
+    // with nothing shared every pair matches, the product
+    let left_tuples = left.data.tuples();
+    let right_tuples = right.data.tuples();
+    let values = hash_join(&left_tuples, &right_tuples, &shared)
+        .into_iter()
+        .map(|(i, j)| {
+            let (l, r) = (&left_tuples[i], &right_tuples[j]);
+            l.iter()
+                .chain(rest.iter().map(|j| &r[*j]))
+                .cloned()
+                .collect::<Row>()
+        })
+        .collect::<Vec<_>>();
+
+    // shared attributes of the right side are found at their left position
+    let split = left_attrs.len();
+    let pk = join_key(left, right, &shared, |j| {
+        match shared.iter().find(|(_, s)| *s == j) {
+            Some((i, _)) => *i,
+            None => split + rest.iter().position(|r| *r == j).unwrap(),
+        }
+    });
+
+    let mut derived = Relation::derived(pk, schema);
+    derived.insert_rows(values)?;
+
+    Ok(derived)
+}
+
+fn division(dividend: &Relation, divisor: &Relation) -> Result<Relation, Error> {
+    let divided = divisor
+        .schema
+        .attributes
+        .iter()
+        .map(|a| {
+            dividend.schema.position(a).map_err(|_| {
+                Error::SchemaMismatch(format!(
+                    "divisor attribute {} {:?} is not part of the dividend",
+                    a.name, a.atype
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let rest = (0..dividend.schema.attributes.len())
+        .filter(|i| !divided.contains(i))
+        .collect::<Vec<_>>();
+
+    let required = divisor.data.tuples().into_iter().collect::<HashSet<_>>();
+
+    // the divisor tuples each group of the remaining attributes appears with
+    let mut groups: Vec<Row> = vec![];
+    let mut seen: HashMap<Row, HashSet<Row>> = HashMap::new();
+    for row in dividend.data.tuples() {
+        let group = rest.iter().map(|i| row[*i].clone()).collect::<Row>();
+        let value = divided.iter().map(|i| row[*i].clone()).collect::<Row>();
+
+        if !seen.contains_key(&group) {
+            groups.push(group.clone());
+        }
+        let found = seen.entry(group).or_default();
+        if required.contains(&value) {
+            found.insert(value);
+        }
+    }
+
+    let values = groups
+        .into_iter()
+        .filter(|group| seen[group].len() == required.len())
+        .collect::<Vec<_>>();
+
+    let pk = dividend.pk.as_ref().and_then(|pk| {
+        pk.iter()
+            .map(|i| rest.iter().position(|r| r == i))
+            .collect::<Option<Vec<_>>>()
+    });
+    let schema = Schema {
+        attributes: rest
+            .iter()
+            .map(|i| dividend.schema.attributes[*i].clone())
+            .collect(),
+    };
+
+    let mut derived = Relation::derived(pk, schema);
+    derived.insert_rows(values)?;
+
+    Ok(derived)
+}
+
+/// Pairs up the tuples of both sides that agree on every `(left, right)`
+/// pair of attribute positions in `on`. The smaller side is loaded into a
+/// hash table keyed on its join attributes and probed with the other, so
+/// the cost grows with the size of the inputs and the result rather than
+/// with their product. Tuples with a NULL join attribute match nothing.
+/// The indices of the pairs come back in the order a nested loop over
+/// `left` and then `right` would produce them.
+fn hash_join(left: &[Row], right: &[Row], on: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let build_left = left.len() <= right.len();
+    let (build, probe) = match build_left {
+        true => (left, right),
+        false => (right, left),
+    };
+    let (build_on, probe_on): (Vec<usize>, Vec<usize>) = on
+        .iter()
+        .map(|(l, r)| match build_left {
+            true => (*l, *r),
+            false => (*r, *l),
+        })
+        .unzip();
+
+    let mut table: HashMap<Key, Vec<usize>> = HashMap::new();
+    for (i, row) in build.iter().enumerate() {
+        let key = build_on.iter().map(|a| row[*a].clone()).collect::<Key>();
+        if !key.contains(&Value::Null) {
+            table.entry(key).or_default().push(i);
+        }
+    }
+
+    let mut pairs = vec![];
+    for (j, row) in probe.iter().enumerate() {
+        let key = probe_on.iter().map(|a| row[*a].clone()).collect::<Key>();
+        if let Some(matches) = table.get(&key) {
+            pairs.extend(matches.iter().map(|i| match build_left {
+                true => (*i, j),
+                false => (j, *i),
+            }));
+        }
+    }
+
+    if build_left {
+        pairs.sort_unstable();
+    }
+
+    pairs
+}
+
+/// Picks the key of a join result from `matched`, the pairs of left and
+/// right attribute positions the join makes equal. When the right key is
+/// fully matched every left tuple joins at most one right tuple, so the
+/// left key still holds, and the other way around. Otherwise the key is
+/// the composite of both. `right_pos` maps a right attribute to its
+/// position in the result.
+fn join_key(
+    left: &Relation,
+    right: &Relation,
+    matched: &[(usize, usize)],
+    right_pos: impl Fn(usize) -> usize,
+) -> Option<Vec<usize>> {
+    let (left_pk, right_pk) = (left.pk.as_ref()?, right.pk.as_ref()?);
+
+    if right_pk.iter().all(|j| matched.iter().any(|(_, m)| m == j)) {
+        return Some(left_pk.clone());
+    }
+
+    if left_pk.iter().all(|i| matched.iter().any(|(m, _)| m == i)) {
+        return Some(right_pk.iter().map(|j| right_pos(*j)).collect());
+    }
+
+    let mut pk = left_pk.clone();
+    for j in right_pk.iter().map(|j| right_pos(*j)) {
+        if !pk.contains(&j) {
+            pk.push(j);
+        }
+    }
+
+    Some(pk)
+}
+
+/// Removes duplicate tuples, keeping the first occurrence of each
+fn distinct(rows: impl IntoIterator<Item = Row>) -> Vec<Row> {
+    let mut seen = HashSet::new();
+    rows.into_iter()
+        .filter(|row| seen.insert(row.clone()))
+        .collect()
+}
+
+/// What an operator works on, either a stored relation or the result of
+/// another operator, so operators nest into a tree evaluated bottom up
+#[derive(Debug)]
+pub enum Input<'a> {
+    Relation(&'a Relation),
+    Operator(Box<Operator<'a>>),
+}
+
+impl Input<'_> {
+    /// The name the relation is known by, for a stored relation and for the
+    /// operators that keep the name of their input or give it a new one
+    pub(crate) fn name(&self) -> Option<&str> {
+        let opr = match self {
+            Input::Relation(r) => return Some(&r.name),
+            Input::Operator(opr) => opr.as_ref(),
+        };
+
+        match opr {
+            Operator::Unary(UnaryOpr::Rename(Some(name), ..)) => Some(name),
+            Operator::Unary(
+                UnaryOpr::Selection(_, input)
+                | UnaryOpr::Projection(_, input)
+                | UnaryOpr::Rename(None, _, input)
+                | UnaryOpr::Sort(_, input)
+                | UnaryOpr::Limit(_, input)
+                | UnaryOpr::Offset(_, input),
+            )
+            | Operator::Binary(
+                BinaryOpr::SemiJoin(_, input, _) | BinaryOpr::AntiJoin(_, input, _),
+            ) => input.name(),
+            _ => None,
+        }
+    }
+
+    /// Borrows a stored relation, or evaluates the operator
+    fn resolve(&self) -> Result<Cow<'_, Relation>, Error> {
+        match self {
+            Input::Relation(r) => Ok(Cow::Borrowed(r)),
+            Input::Operator(opr) => opr.evaluate().map(Cow::Owned),
+        }
+    }
+}
+
+impl<'a> From<&'a Relation> for Input<'a> {
+    fn from(relation: &'a Relation) -> Self {
+        Input::Relation(relation)
+    }
+}
+
+impl<'a> From<Operator<'a>> for Input<'a> {
+    fn from(opr: Operator<'a>) -> Self {
+        Input::Operator(Box::new(opr))
+    }
+}
+
+#[derive(Debug)]
+pub enum Operator<'a> {
+    Unary(UnaryOpr<'a>),
+    Binary(BinaryOpr<'a>),
+}
+
+impl Operator<'_> {
+    pub fn evaluate(&self) -> Result<Relation, Error> {
+        match self {
+            Operator::Unary(opr) => opr.evaluate(),
+            Operator::Binary(opr) => opr.evaluate(),
+        }
+    }
+}
+
+/// A catalog of relations, each stored under its name
+#[derive(Debug, Default)]
+pub struct Database {
+    relations: BTreeMap<String, Relation>,
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Database::default()
+    }
+
+    /// Adds `relation` under its name, which no other relation may have
+    pub fn create(&mut self, relation: Relation) -> Result<(), Error> {
+        if self.relations.contains_key(&relation.name) {
+            return Err(Error::DuplicateRelation(relation.name));
+        }
+
+        self.relations.insert(relation.name.clone(), relation);
+        Ok(())
+    }
+
+    /// Removes the relation called `name` and gives it back
+    pub fn remove(&mut self, name: &str) -> Result<Relation, Error> {
+        self.relations
+            .remove(name)
+            .ok_or_else(|| Error::UnknownRelation(name.to_string()))
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Relation, Error> {
+        self.relations
+            .get(name)
+            .ok_or_else(|| Error::UnknownRelation(name.to_string()))
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Result<&mut Relation, Error> {
+        self.relations
+            .get_mut(name)
+            .ok_or_else(|| Error::UnknownRelation(name.to_string()))
+    }
+
+    /// Names of the relations, in order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.relations.keys().map(String::as_str)
+    }
+
+    /// The relation called `name` as the input of an operator
+    pub fn input(&self, name: &str) -> Result<Input<'_>, Error> {
+        self.get(name).map(Input::Relation)
+    }
+
+    /// Evaluates relational algebra text over the relations, see
+    /// [`parser`]
+    pub fn query(&self, src: &str) -> Result<Relation, Error> {
+        parser::parse(src, &self.relations.values().collect::<Vec<_>>())?.evaluate()
+    }
+
+    /// Evaluates a SQL `SELECT` over the relations, see [`sql`]
+    pub fn sql(&self, src: &str) -> Result<Relation, Error> {
+        sql::parse(src, &self.relations.values().collect::<Vec<_>>())?.evaluate()
+    }
+
+    /// Runs a SQL statement, giving the result of a `SELECT` and nothing for
+    /// the others
+    pub fn execute(&mut self, src: &str) -> Result<Option<Relation>, Error> {
+        match sql::statement(src)? {
+            sql::Statement::Select(plan) => {
+                let relations = self.relations.values().collect::<Vec<_>>();
+                return plan.bind(&relations)?.evaluate().map(Some);
+            }
+            sql::Statement::CreateTable(relation) => self.create(relation)?,
+            sql::Statement::DropTable(name) => drop(self.remove(&name)?),
+            sql::Statement::AlterTable(name, alteration) => {
+                let relation = self.get_mut(&name)?;
+                match alteration {
+                    sql::Alteration::AddColumn(attr) => relation.add_attribute(attr)?,
+                    sql::Alteration::DropColumn(name) => relation.drop_attribute(&name)?,
+                    sql::Alteration::RenameColumn(from, to) => {
+                        relation.rename_attribute(&from, &to)?
+                    }
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+pub fn add(left: u64, right: u64) -> u64 {
+    left + right
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use super::*;
+
+    fn create_test_schema() -> Schema {
+        Schema {
+            attributes: vec![
+                Attribute {
+                    name: "key".to_string(),
+                    atype: Type::Int,
+                    nullable: false,
+                },
+                Attribute {
+                    name: "value".to_string(),
+                    atype: Type::Str,
+                    nullable: false,
+                },
+            ],
+        }
+    }
+
+    fn create_test_relation() -> Relation {
+        let schema = Schema {
+            attributes: vec![
+                Attribute {
+                    name: "key".to_string(),
+                    atype: Type::Int,
+                    nullable: false,
+                },
+                Attribute {
+                    name: "value".to_string(),
+                    atype: Type::Str,
+                    nullable: false,
+                },
+            ],
+        };
+
+        Relation {
+            name: "test".to_string(),
+            pk: Some(vec![0]),
+            // fks: None,
+            schema,
+            data: Data::WithPK(Box::default()),
+        }
+    }
+
+    #[test]
+    fn it_works() {
+        let result = add(2, 2);
+        assert_eq!(result, 4);
+    }
+
+    #[test]
+    fn validate_invalid_row_schema() {
+        let schema = create_test_schema();
+
+        assert!(!schema.validate_row(&vec![Value::Str("foo".to_string()), Value::Int(1)]))
+    }
+
+    #[test]
     fn validate_row_schema() {
         let schema = create_test_schema();
 
+        assert!(schema.validate_row(&vec![Value::Int(1), Value::Str("foo".to_string())]))
+    }
+
+    #[test]
+    fn test_insert_row() {
+        let mut relation = create_test_relation();
+
+        assert!(
+            relation
+                .insert_row(vec![Value::Int(1), Value::Str("foo".to_string())])
+                .is_ok()
+        );
+
+        println!("[TEST] data inserted: {:?}", &relation.data);
+
+        assert!(matches!(
+            relation.insert_row(vec![Value::Int(1), Value::Str("bar".to_string())]),
+            Err(Error::DuplicatePrimaryKey(_))
+        ));
+
+        println!("[TEST] duplicate row not inserted");
+
+        assert!(
+            relation
+                .insert_rows(vec![
+                    vec![Value::Int(2), Value::Str("foo".to_string())],
+                    vec![Value::Int(3), Value::Str("bar".to_string())],
+                ])
+                .is_ok()
+        );
+
+        println!("[TEST] multiple inserts {:?}", &relation.data);
+
+        assert!(matches!(
+            relation.insert_rows(vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(2), Value::Str("bar".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+            ]),
+            Err(Error::DuplicatePrimaryKey(_))
+        ));
+
+        println!("[TEST] not inserting rows if duplicates found");
+
+        assert!(
+            relation
+                .insert_rows(vec![
+                    vec![Value::Int(4), Value::Str("apple".to_string())],
+                    vec![Value::Int(5), Value::Str("orange".to_string())],
+                    vec![Value::Int(6), Value::Str("orange".to_string())],
+                ])
+                .is_ok()
+        );
+
+        println!("[TEST] multiple inserts {:?}", &relation.data);
+    }
+
+    #[test]
+    fn basic_projections() {
+        let mut relation = create_test_relation();
+
+        relation
+            .insert_rows(vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(2), Value::Str("bar".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+            ])
+            .unwrap();
+
+        let select_all = Operator::Unary(UnaryOpr::Projection(
+            ProjAttrs::None,
+            Input::Relation(&relation),
+        ));
+        let result = select_all.evaluate();
+
+        assert!(result.is_ok());
+
+        assert_eq!(
+            result.as_ref().unwrap().data.tuples(),
+            vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(2), Value::Str("bar".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+            ]
+        );
+
+        println!("[TEST] query result: {:?}", result.unwrap());
+
+        let select_value_attr = Operator::Unary(UnaryOpr::Projection(
+            ProjAttrs::Attr(
+                Attribute {
+                    name: "value".to_string(),
+                    atype: Type::Str,
+                    nullable: false,
+                },
+                None,
+            ),
+            Input::Relation(&relation),
+        ));
+
+        let result = select_value_attr.evaluate();
+        assert!(result.is_ok());
+        let mut left = result.as_ref().unwrap().data.tuples();
+        let mut right = vec![
+            vec![Value::Str("foo".to_string())],
+            vec![Value::Str("bar".to_string())],
+            vec![Value::Str("baz".to_string())],
+        ];
+
+        left.sort();
+        right.sort();
+        assert_eq!(left, right);
+
+        println!("[TEST] selecting a single attribute {:?}", result);
+    }
+
+    #[test]
+    fn test_remove_duplicates() {
+        let mut relation = Relation {
+            name: "pk_less".to_string(),
+            pk: Some(vec![0]),
+            schema: Schema {
+                attributes: vec![
+                    Attribute {
+                        name: "id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    Attribute {
+                        name: "value".to_string(),
+                        atype: Type::Str,
+                        nullable: false,
+                    },
+                ],
+            },
+            data: Data::WithPK(Box::default()),
+        };
+
+        let insert_result = relation.insert_rows(vec![
+            vec![Value::Int(1), Value::Str("foo".to_string())],
+            vec![Value::Int(2), Value::Str("bar".to_string())],
+            vec![Value::Int(3), Value::Str("baz".to_string())],
+            vec![Value::Int(4), Value::Str("foo".to_string())],
+        ]);
+
+        assert!(insert_result.is_ok());
+        assert_eq!(
+            relation.data.tuples(),
+            vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(2), Value::Str("bar".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+                vec![Value::Int(4), Value::Str("foo".to_string())],
+            ]
+        );
+
+        let query = Operator::Unary(UnaryOpr::Projection(
+            ProjAttrs::Attr(
+                Attribute {
+                    name: "value".to_string(),
+                    atype: Type::Str,
+                    nullable: false,
+                },
+                None,
+            ),
+            Input::Relation(&relation),
+        ));
+        let result = query.evaluate();
+        assert!(result.is_ok());
+
+        let mut left = result.as_ref().unwrap().data.tuples();
+        let mut right = vec![
+            vec![Value::Str("foo".to_string())],
+            vec![Value::Str("bar".to_string())],
+            vec![Value::Str("baz".to_string())],
+        ];
+
+        left.sort();
+        right.sort();
+        assert_eq!(left, right);
+
+        println!("[test] Project removed duplicate tuples");
+
+        let derived = result.unwrap();
+        assert!(derived.pk.is_none());
+
+        let derived_query = Operator::Unary(UnaryOpr::Projection(
+            ProjAttrs::None,
+            Input::Relation(&derived),
+        ));
+        let derived_query_result = derived_query.evaluate();
+
+        assert!(derived_query_result.is_ok());
+
+        let mut left = derived_query_result.as_ref().unwrap().data.tuples();
+        let mut right = vec![
+            vec![Value::Str("foo".to_string())],
+            vec![Value::Str("bar".to_string())],
+            vec![Value::Str("baz".to_string())],
+        ];
+
+        left.sort();
+        right.sort();
+        assert_eq!(left, right);
+        assert!(derived_query_result.unwrap().pk.is_none());
+
+        println!("[test] Project removed duplicate tuples");
+    }
+
+    #[test]
+    fn test_user_schema() {
+        // tbl users
+        // | id INT PK | name STR | phone INT
+        let mut relation = Relation {
+            name: "users".to_string(),
+            pk: Some(vec![0]),
+            schema: Schema {
+                attributes: vec![
+                    Attribute {
+                        name: "id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    Attribute {
+                        name: "name".to_string(),
+                        atype: Type::Str,
+                        nullable: false,
+                    },
+                    Attribute {
+                        name: "phone".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                ],
+            },
+            data: Data::WithPK(Box::default()),
+        };
+
+        // 100 | bob | 9999999999
+        // 101 | alice | 6666666666
+        let insert_result = relation.insert_rows(vec![
+            vec![
+                Value::Int(100),
+                Value::Str("bob".to_string()),
+                Value::Int(9999999999),
+            ],
+            vec![
+                Value::Int(101),
+                Value::Str("alice".to_string()),
+                Value::Int(6666666666),
+            ],
+        ]);
+        assert!(insert_result.is_ok());
+
+        // pi_{name, phone}
+        let query = Operator::Unary(UnaryOpr::Projection(
+            ProjAttrs::Attr(
+                Attribute {
+                    name: "name".to_string(),
+                    atype: Type::Str,
+                    nullable: false,
+                },
+                Some(Box::new(ProjAttrs::Attr(
+                    Attribute {
+                        name: "phone".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    None,
+                ))),
+            ),
+            Input::Relation(&relation),
+        ));
+
+        let result = query.evaluate();
+
+        // tbl derived
+        // bob | 9999999999
+        // alice | 6666666666
+        let mut left = result.as_ref().unwrap().data.tuples();
+        let mut right = vec![
+            vec![Value::Str("bob".to_string()), Value::Int(9999999999)],
+            vec![Value::Str("alice".to_string()), Value::Int(6666666666)],
+        ];
+
+        left.sort();
+        right.sort();
+        assert_eq!(left, right);
+    }
+
+    fn key_condition(
+        comp: Comp,
+        key: i64,
+        next: Option<(Connective, Box<SelPredicate>)>,
+    ) -> SelPredicate {
+        SelPredicate::Condition(
+            (
+                Attribute {
+                    name: "key".to_string(),
+                    atype: Type::Int,
+                    nullable: false,
+                },
+                comp,
+                Operand::Value(Value::Int(key)),
+            ),
+            next,
+        )
+    }
+
+    #[test]
+    fn basic_selections() {
+        let mut relation = create_test_relation();
+
+        relation
+            .insert_rows(vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(2), Value::Str("bar".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+            ])
+            .unwrap();
+
+        let keys = |comp: Comp| {
+            let query = Operator::Unary(UnaryOpr::Selection(
+                key_condition(comp, 2, None),
+                Input::Relation(&relation),
+            ));
+            let result = query.evaluate();
+            assert!(result.is_ok());
+            result
+                .unwrap()
+                .data
+                .tuples()
+                .iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(keys(Comp::GT), vec![Value::Int(3)]);
+        assert_eq!(keys(Comp::LT), vec![Value::Int(1)]);
+        assert_eq!(keys(Comp::GE), vec![Value::Int(2), Value::Int(3)]);
+        assert_eq!(keys(Comp::LE), vec![Value::Int(1), Value::Int(2)]);
+        assert_eq!(keys(Comp::EQ), vec![Value::Int(2)]);
+        assert_eq!(keys(Comp::NE), vec![Value::Int(1), Value::Int(3)]);
+
+        // σ_{value = "baz"}
+        let query = Operator::Unary(UnaryOpr::Selection(
+            SelPredicate::Condition(
+                (
+                    Attribute {
+                        name: "value".to_string(),
+                        atype: Type::Str,
+                        nullable: false,
+                    },
+                    Comp::EQ,
+                    Operand::Value(Value::Str("baz".to_string())),
+                ),
+                None,
+            ),
+            Input::Relation(&relation),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert_eq!(result.pk, relation.pk);
+        assert_eq!(result.schema.attributes, relation.schema.attributes);
+        assert_eq!(
+            result.data.tuples(),
+            vec![vec![Value::Int(3), Value::Str("baz".to_string())]]
+        );
+
+        let select_all = Operator::Unary(UnaryOpr::Selection(
+            SelPredicate::None,
+            Input::Relation(&relation),
+        ));
+        assert_eq!(
+            select_all.evaluate().unwrap().data.tuples(),
+            relation.data.tuples()
+        );
+    }
+
+    #[test]
+    fn selection_connectives() {
+        let mut relation = create_test_relation();
+
+        relation
+            .insert_rows(vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(2), Value::Str("bar".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+                vec![Value::Int(4), Value::Str("qux".to_string())],
+            ])
+            .unwrap();
+
+        // σ_{key > 1 AND key < 4}
+        let query = Operator::Unary(UnaryOpr::Selection(
+            key_condition(
+                Comp::GT,
+                1,
+                Some((Connective::AND, Box::new(key_condition(Comp::LT, 4, None)))),
+            ),
+            Input::Relation(&relation),
+        ));
+        assert_eq!(
+            query.evaluate().unwrap().data.tuples(),
+            vec![
+                vec![Value::Int(2), Value::Str("bar".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+            ]
+        );
+
+        // σ_{key = 1 OR key > 2 AND key < 4} ≡ σ_{key = 1 OR (key > 2 AND key < 4)}
+        let query = Operator::Unary(UnaryOpr::Selection(
+            key_condition(
+                Comp::EQ,
+                1,
+                Some((
+                    Connective::OR,
+                    Box::new(key_condition(
+                        Comp::GT,
+                        2,
+                        Some((Connective::AND, Box::new(key_condition(Comp::LT, 4, None)))),
+                    )),
+                )),
+            ),
+            Input::Relation(&relation),
+        ));
+        assert_eq!(
+            query.evaluate().unwrap().data.tuples(),
+            vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+            ]
+        );
+
+        // σ_{key > 2 AND key < 4 OR key = 1} groups the same way
+        let query = Operator::Unary(UnaryOpr::Selection(
+            key_condition(
+                Comp::GT,
+                2,
+                Some((
+                    Connective::AND,
+                    Box::new(key_condition(
+                        Comp::LT,
+                        4,
+                        Some((Connective::OR, Box::new(key_condition(Comp::EQ, 1, None)))),
+                    )),
+                )),
+            ),
+            Input::Relation(&relation),
+        ));
+        assert_eq!(
+            query.evaluate().unwrap().data.tuples(),
+            vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_selection() {
+        let mut relation = create_test_relation();
+
+        relation
+            .insert_rows(vec![vec![Value::Int(1), Value::Str("foo".to_string())]])
+            .unwrap();
+
+        // comparing an INT attribute against a STR value
+        let query = Operator::Unary(UnaryOpr::Selection(
+            SelPredicate::Condition(
+                (
+                    Attribute {
+                        name: "key".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    Comp::EQ,
+                    Operand::Value(Value::Str("foo".to_string())),
+                ),
+                None,
+            ),
+            Input::Relation(&relation),
+        ));
+        assert!(matches!(query.evaluate(), Err(Error::TypeMismatch(_))));
+
+        // attribute missing from the schema
+        let query = Operator::Unary(UnaryOpr::Selection(
+            SelPredicate::Condition(
+                (
+                    Attribute {
+                        name: "phone".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    Comp::EQ,
+                    Operand::Value(Value::Int(1)),
+                ),
+                None,
+            ),
+            Input::Relation(&relation),
+        ));
+        assert_eq!(
+            query.evaluate().unwrap_err(),
+            Error::UnknownAttribute("phone".to_string())
+        );
+    }
+
+    #[test]
+    fn union() {
+        let mut left = create_test_relation();
+        let mut right = create_test_relation();
+
+        left.insert_rows(vec![
+            vec![Value::Int(1), Value::Str("foo".to_string())],
+            vec![Value::Int(2), Value::Str("bar".to_string())],
+        ])
+        .unwrap();
+        right
+            .insert_rows(vec![
+                vec![Value::Int(2), Value::Str("bar".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+            ])
+            .unwrap();
+
+        let query = Operator::Binary(BinaryOpr::Union(
+            Input::Relation(&left),
+            Input::Relation(&right),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert_eq!(result.pk, Some(vec![0]));
+        assert_eq!(
+            result.data.tuples(),
+            vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(2), Value::Str("bar".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+            ]
+        );
+
+        // the same key on a different tuple, the union can no longer be keyed
+        right
+            .insert_row(vec![Value::Int(1), Value::Str("qux".to_string())])
+            .unwrap();
+
+        let query = Operator::Binary(BinaryOpr::Union(
+            Input::Relation(&left),
+            Input::Relation(&right),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert!(result.pk.is_none());
+        let mut tuples = result.data.tuples();
+        tuples.sort();
+        assert_eq!(
+            tuples,
+            vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(1), Value::Str("qux".to_string())],
+                vec![Value::Int(2), Value::Str("bar".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+            ]
+        );
+
+        // a NULL on the right fits the union of a NOT NULL attribute on the left
+        right.pk = None;
+        right.data = Data::NoPK((0, Box::default()));
+        right.schema.attributes[1].nullable = true;
+        right.insert_row(vec![Value::Int(4), Value::Null]).unwrap();
+
+        let query = Operator::Binary(BinaryOpr::Union(
+            Input::Relation(&left),
+            Input::Relation(&right),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert!(result.schema.attributes[1].nullable);
+        assert!(!result.schema.attributes[0].nullable);
+        assert_eq!(
+            result.data.tuples().last(),
+            Some(&vec![Value::Int(4), Value::Null])
+        );
+    }
+
+    #[test]
+    fn union_incompatible_schemas() {
+        let left = create_test_relation();
+        let mut right = create_test_relation();
+
+        // same arity, attribute types swapped
+        right.schema.attributes.reverse();
+        let query = Operator::Binary(BinaryOpr::Union(
+            Input::Relation(&left),
+            Input::Relation(&right),
+        ));
+        assert!(matches!(query.evaluate(), Err(Error::SchemaMismatch(_))));
+
+        // different arity
+        right.schema.attributes.pop();
+        let query = Operator::Binary(BinaryOpr::Union(
+            Input::Relation(&left),
+            Input::Relation(&right),
+        ));
+        assert!(matches!(query.evaluate(), Err(Error::SchemaMismatch(_))));
+    }
+
+    #[test]
+    fn difference_and_intersection() {
+        // yesterday's snapshot is keyed, today's is a plain bag of tuples
+        let mut yesterday = create_test_relation();
+        let mut today = create_test_relation();
+        today.pk = None;
+        today.data = Data::NoPK((0, Box::default()));
+
+        yesterday
+            .insert_rows(vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(2), Value::Str("bar".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+            ])
+            .unwrap();
+        today
+            .insert_rows(vec![
+                vec![Value::Int(2), Value::Str("bar".to_string())],
+                vec![Value::Int(3), Value::Str("qux".to_string())],
+                vec![Value::Int(4), Value::Str("foo".to_string())],
+                vec![Value::Int(4), Value::Str("foo".to_string())],
+            ])
+            .unwrap();
+
+        let query = Operator::Binary(BinaryOpr::Difference(
+            Input::Relation(&yesterday),
+            Input::Relation(&today),
+        ));
+        let result = query.evaluate().unwrap();
+        assert_eq!(result.pk, Some(vec![0]));
+        assert_eq!(
+            result.data.tuples(),
+            vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(3), Value::Str("baz".to_string())],
+            ]
+        );
+
+        // duplicates on the left side are collapsed
+        let query = Operator::Binary(BinaryOpr::Difference(
+            Input::Relation(&today),
+            Input::Relation(&yesterday),
+        ));
+        let result = query.evaluate().unwrap();
+        assert!(result.pk.is_none());
+        assert_eq!(
+            result.data.tuples(),
+            vec![
+                vec![Value::Int(3), Value::Str("qux".to_string())],
+                vec![Value::Int(4), Value::Str("foo".to_string())],
+            ]
+        );
+
+        // the key of the right side carries over when the left has none
+        for query in [
+            Operator::Binary(BinaryOpr::Intersection(
+                Input::Relation(&yesterday),
+                Input::Relation(&today),
+            )),
+            Operator::Binary(BinaryOpr::Intersection(
+                Input::Relation(&today),
+                Input::Relation(&yesterday),
+            )),
+        ] {
+            let result = query.evaluate().unwrap();
+            assert_eq!(result.pk, Some(vec![0]));
+            assert_eq!(
+                result.data.tuples(),
+                vec![vec![Value::Int(2), Value::Str("bar".to_string())]]
+            );
+        }
+
+        let mut other = create_test_relation();
+        other.schema.attributes.pop();
+        let query = Operator::Binary(BinaryOpr::Intersection(
+            Input::Relation(&yesterday),
+            Input::Relation(&other),
+        ));
+        assert!(matches!(query.evaluate(), Err(Error::SchemaMismatch(_))));
+    }
+
+    fn create_users_and_orders() -> (Relation, Relation) {
+        let mut users = Relation {
+            name: "users".to_string(),
+            pk: Some(vec![0]),
+            schema: Schema {
+                attributes: vec![
+                    Attribute {
+                        name: "id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    Attribute {
+                        name: "name".to_string(),
+                        atype: Type::Str,
+                        nullable: false,
+                    },
+                ],
+            },
+            data: Data::WithPK(Box::default()),
+        };
+        let mut orders = Relation {
+            name: "orders".to_string(),
+            pk: Some(vec![0]),
+            schema: Schema {
+                attributes: vec![
+                    Attribute {
+                        name: "id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    Attribute {
+                        name: "user_id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    Attribute {
+                        name: "item".to_string(),
+                        atype: Type::Str,
+                        nullable: false,
+                    },
+                ],
+            },
+            data: Data::WithPK(Box::default()),
+        };
+
+        users
+            .insert_rows(vec![
+                vec![Value::Int(1), Value::Str("bob".to_string())],
+                vec![Value::Int(2), Value::Str("alice".to_string())],
+            ])
+            .unwrap();
+        orders
+            .insert_rows(vec![
+                vec![Value::Int(10), Value::Int(1), Value::Str("pen".to_string())],
+                vec![Value::Int(11), Value::Int(1), Value::Str("ink".to_string())],
+                vec![Value::Int(12), Value::Int(2), Value::Str("pad".to_string())],
+            ])
+            .unwrap();
+
+        (users, orders)
+    }
+
+    #[test]
+    fn cartesian_product() {
+        let (users, orders) = create_users_and_orders();
+
+        let query = Operator::Binary(BinaryOpr::Product(
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert_eq!(
+            result
+                .schema
+                .attributes
+                .iter()
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "users.id",
+                "users.name",
+                "orders.id",
+                "orders.user_id",
+                "orders.item"
+            ]
+        );
+        assert_eq!(result.pk, Some(vec![0, 2]));
+        assert_eq!(result.data.tuples().len(), 6);
+        assert_eq!(
+            result.data.tuples()[0],
+            vec![
+                Value::Int(1),
+                Value::Str("bob".to_string()),
+                Value::Int(10),
+                Value::Int(1),
+                Value::Str("pen".to_string()),
+            ]
+        );
+
+        // `item` only exists on one side, `id` needs its qualifier
+        let item = Attribute {
+            name: "item".to_string(),
+            atype: Type::Str,
+            nullable: false,
+        };
+        let id = Attribute {
+            name: "id".to_string(),
+            atype: Type::Int,
+            nullable: false,
+        };
+        assert_eq!(result.schema.position(&item), Ok(4));
+        assert_eq!(
+            result.schema.position(&id),
+            Err(Error::AmbiguousAttribute("id".to_string()))
+        );
+        assert_eq!(result.schema.position(&id.qualified("orders")), Ok(2));
+
+        let query = Operator::Unary(UnaryOpr::Selection(
+            SelPredicate::Condition(
+                (
+                    item,
+                    Comp::EQ,
+                    Operand::Value(Value::Str("pad".to_string())),
+                ),
+                None,
+            ),
+            Input::Relation(&result),
+        ));
+        let mut ids = query
+            .evaluate()
+            .unwrap()
+            .data
+            .tuples()
+            .into_iter()
+            .map(|row| row[0].clone())
+            .collect::<Vec<_>>();
+        ids.sort();
+        assert_eq!(ids, vec![Value::Int(1), Value::Int(2)]);
+
+        // a relation with itself, whose sides only differ once renamed
+        let query = Operator::Binary(BinaryOpr::Product(
+            Input::Relation(&users),
+            Input::Relation(&users),
+        ));
+        assert!(matches!(
+            query.evaluate(),
+            Err(Error::DuplicateAttribute(name)) if name.starts_with("users.id")
+        ));
+        let query = Operator::Binary(BinaryOpr::Product(
+            Input::Relation(&users),
+            Operator::Unary(UnaryOpr::Rename(
+                Some("other".to_string()),
+                vec![],
+                Input::Relation(&users),
+            ))
+            .into(),
+        ));
+        assert_eq!(query.evaluate().unwrap().data.tuples().len(), 4);
+    }
+
+    #[test]
+    fn natural_join() {
+        let (users, orders) = create_users_and_orders();
+
+        let mut items = Relation {
+            name: "items".to_string(),
+            pk: Some(vec![0]),
+            schema: Schema {
+                attributes: vec![
+                    Attribute {
+                        name: "item".to_string(),
+                        atype: Type::Str,
+                        nullable: false,
+                    },
+                    Attribute {
+                        name: "price".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                ],
+            },
+            data: Data::WithPK(Box::default()),
+        };
+        items
+            .insert_rows(vec![
+                vec![Value::Str("pen".to_string()), Value::Int(5)],
+                vec![Value::Str("pad".to_string()), Value::Int(3)],
+            ])
+            .unwrap();
+
+        // joined on the key of items, so every order keeps its own key
+        let query = Operator::Binary(BinaryOpr::NaturalJoin(
+            Input::Relation(&orders),
+            Input::Relation(&items),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert_eq!(
+            result
+                .schema
+                .attributes
+                .iter()
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["id", "user_id", "item", "price"]
+        );
+        assert_eq!(result.pk, Some(vec![0]));
+        assert_eq!(
+            result.data.tuples(),
+            vec![
+                vec![
+                    Value::Int(10),
+                    Value::Int(1),
+                    Value::Str("pen".to_string()),
+                    Value::Int(5),
+                ],
+                vec![
+                    Value::Int(12),
+                    Value::Int(2),
+                    Value::Str("pad".to_string()),
+                    Value::Int(3),
+                ],
+            ]
+        );
+
+        // the other way around the key of orders is found after the join
+        let query = Operator::Binary(BinaryOpr::NaturalJoin(
+            Input::Relation(&items),
+            Input::Relation(&orders),
+        ));
+        let result = query.evaluate().unwrap();
+        assert_eq!(result.pk, Some(vec![2]));
+        assert_eq!(result.data.tuples().len(), 2);
+
+        // users and orders share `id`, which means different things on
+        // each side, but a natural join only goes by name
+        let query = Operator::Binary(BinaryOpr::NaturalJoin(
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        let result = query.evaluate().unwrap();
+        assert_eq!(result.pk, Some(vec![0]));
+        assert!(result.data.tuples().is_empty());
+
+        // a name of different types on the two sides of a self join is
+        // qualified the same way on both
+        let relations = [&users];
+        let query =
+            parser::parse("π[id, name](users) ⋈ π[id, 1 AS name](users)", &relations).unwrap();
+        assert_eq!(
+            query.evaluate().unwrap_err(),
+            Error::DuplicateAttribute(
+                "users.name, rename one of the relations to tell them apart".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn theta_join() {
+        let (users, orders) = create_users_and_orders();
+
+        let user_id = |comp: Comp| {
+            SelPredicate::Condition(
+                (
+                    Attribute {
+                        name: "users.id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    comp,
+                    Operand::Attr(Attribute {
+                        name: "user_id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    }),
+                ),
+                None,
+            )
+        };
+
+        // ⋈_{users.id = user_id}, every order belongs to a single user
+        let query = Operator::Binary(BinaryOpr::ThetaJoin(
+            user_id(Comp::EQ),
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert_eq!(result.pk, Some(vec![2]));
+        assert_eq!(
+            result.data.tuples(),
+            vec![
+                vec![
+                    Value::Int(1),
+                    Value::Str("bob".to_string()),
+                    Value::Int(10),
+                    Value::Int(1),
+                    Value::Str("pen".to_string()),
+                ],
+                vec![
+                    Value::Int(1),
+                    Value::Str("bob".to_string()),
+                    Value::Int(11),
+                    Value::Int(1),
+                    Value::Str("ink".to_string()),
+                ],
+                vec![
+                    Value::Int(2),
+                    Value::Str("alice".to_string()),
+                    Value::Int(12),
+                    Value::Int(2),
+                    Value::Str("pad".to_string()),
+                ],
+            ]
+        );
+
+        // ⋈_{users.id > user_id}, no key is matched so both are needed
+        let query = Operator::Binary(BinaryOpr::ThetaJoin(
+            user_id(Comp::GT),
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert_eq!(result.pk, Some(vec![0, 2]));
+        assert_eq!(
+            result
+                .data
+                .tuples()
+                .iter()
+                .map(|row| (row[0].clone(), row[2].clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Value::Int(2), Value::Int(10)),
+                (Value::Int(2), Value::Int(11)),
+            ]
+        );
+
+        // comparing an INT attribute with a STR one
+        let query = Operator::Binary(BinaryOpr::ThetaJoin(
+            SelPredicate::Condition(
+                (
+                    Attribute {
+                        name: "users.id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    Comp::EQ,
+                    Operand::Attr(Attribute {
+                        name: "item".to_string(),
+                        atype: Type::Str,
+                        nullable: false,
+                    }),
+                ),
+                None,
+            ),
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        assert!(matches!(query.evaluate(), Err(Error::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn hash_join() {
+        let relation = |name: &str, attrs: [&str; 2], rows: Vec<Row>| {
+            let mut relation = Relation {
+                name: name.to_string(),
+                pk: Some(vec![0]),
+                schema: Schema {
+                    attributes: attrs
+                        .iter()
+                        .map(|a| Attribute {
+                            name: a.to_string(),
+                            atype: Type::Int,
+                            nullable: false,
+                        })
+                        .collect(),
+                },
+                data: Data::WithPK(Box::default()),
+            };
+            assert!(relation.insert_rows(rows).is_ok());
+            relation
+        };
+
+        // 100k ids on each side, a nested loop would need 10^10 comparisons
+        let left = relation(
+            "l",
+            ["id", "x"],
+            (0..100_000)
+                .map(|i| vec![Value::Int(i), Value::Int(i % 7)])
+                .collect(),
+        );
+        let right = relation(
+            "r",
+            ["id", "y"],
+            (0..100_000)
+                .map(|i| vec![Value::Int(i * 2), Value::Int(i % 5)])
+                .collect(),
+        );
+
+        let query = Operator::Binary(BinaryOpr::NaturalJoin(
+            Input::Relation(&left),
+            Input::Relation(&right),
+        ));
+        let result = query.evaluate().unwrap();
+        assert_eq!(result.data.tuples().len(), 50_000);
+        assert_eq!(
+            result.data.tuples()[1],
+            vec![Value::Int(2), Value::Int(2), Value::Int(1)]
+        );
+
+        // l.id = r.id AND l.x = r.y, the second equality is part of the key
+        let equal = |a: &str, b: &str| {
+            (
+                Attribute {
+                    name: a.to_string(),
+                    atype: Type::Int,
+                    nullable: false,
+                },
+                Comp::EQ,
+                Operand::Attr(Attribute {
+                    name: b.to_string(),
+                    atype: Type::Int,
+                    nullable: false,
+                }),
+            )
+        };
+        let query = Operator::Binary(BinaryOpr::ThetaJoin(
+            SelPredicate::Condition(
+                equal("l.id", "r.id"),
+                Some((
+                    Connective::AND,
+                    Box::new(SelPredicate::Condition(equal("x", "y"), None)),
+                )),
+            ),
+            Input::Relation(&left),
+            Input::Relation(&right),
+        ));
+        let result = query.evaluate().unwrap();
+        assert!(
+            result
+                .data
+                .tuples()
+                .iter()
+                .all(|row| row[0] == row[2] && row[1] == row[3])
+        );
+        assert_eq!(
+            result.data.tuples().len(),
+            (0..50_000).filter(|k| (2 * k) % 7 == k % 5).count()
+        );
+
+        // with a residual condition, and the smaller input on the left
+        let small = relation(
+            "s",
+            ["id", "z"],
+            vec![
+                vec![Value::Int(4), Value::Int(0)],
+                vec![Value::Int(3), Value::Int(0)],
+                vec![Value::Int(2), Value::Int(9)],
+            ],
+        );
+        let query = Operator::Binary(BinaryOpr::ThetaJoin(
+            SelPredicate::Condition(
+                equal("s.id", "r.id"),
+                Some((
+                    Connective::AND,
+                    Box::new(SelPredicate::Condition(
+                        (
+                            Attribute {
+                                name: "z".to_string(),
+                                atype: Type::Int,
+                                nullable: false,
+                            },
+                            Comp::LT,
+                            Operand::Value(Value::Int(5)),
+                        ),
+                        None,
+                    )),
+                )),
+            ),
+            Input::Relation(&small),
+            Input::Relation(&right),
+        ));
+        let result = query.evaluate().unwrap();
+        assert_eq!(result.pk, Some(vec![0]));
+        assert_eq!(
+            result.data.tuples(),
+            vec![vec![
+                Value::Int(4),
+                Value::Int(0),
+                Value::Int(4),
+                Value::Int(2)
+            ]]
+        );
+    }
+
+    #[test]
+    fn rename() {
+        let (users, _) = create_users_and_orders();
+
+        // ρ_{managers(id -> manager_id)}
+        let query = Operator::Unary(UnaryOpr::Rename(
+            Some("managers".to_string()),
+            vec![("id".to_string(), "manager_id".to_string())],
+            Input::Relation(&users),
+        ));
+        let managers = query.evaluate().unwrap();
+
+        assert_eq!(managers.name, "managers");
+        assert_eq!(managers.pk, users.pk);
+        assert_eq!(
+            managers
+                .schema
+                .attributes
+                .iter()
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["manager_id", "name"]
+        );
+        assert_eq!(managers.data.tuples(), users.data.tuples());
+
+        // a self join can now tell its two inputs apart
+        let query = Operator::Binary(BinaryOpr::Product(
+            Input::Relation(&users),
+            Input::Relation(&managers),
+        ));
+        let result = query.evaluate().unwrap();
+        assert_eq!(
+            result
+                .schema
+                .attributes
+                .iter()
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "users.id",
+                "users.name",
+                "managers.manager_id",
+                "managers.name"
+            ]
+        );
+
+        // swapping two names
+        let query = Operator::Unary(UnaryOpr::Rename(
+            None,
+            vec![
+                ("id".to_string(), "name".to_string()),
+                ("name".to_string(), "id".to_string()),
+            ],
+            Input::Relation(&users),
+        ));
+        let swapped = query.evaluate().unwrap();
+        assert_eq!(swapped.name, "users");
+        assert_eq!(swapped.schema.attributes[0].name, "name");
+        assert_eq!(swapped.schema.attributes[1].name, "id");
+
+        // a filtered or projected relation keeps its name, so a join still
+        // qualifies its attributes by it
+        let (users, orders) = create_users_and_orders();
+        let relations = [&users, &orders];
+        let query = parser::parse(
+            "σ[id > 1](users) join[users.id = orders.user_id] π[user_id, item](orders)",
+            &relations,
+        )
+        .unwrap();
+        assert_eq!(
+            query.evaluate().unwrap().data.tuples(),
+            vec![vec![
+                Value::Int(2),
+                Value::Str("alice".to_string()),
+                Value::Int(2),
+                Value::Str("pad".to_string())
+            ]]
+        );
+        let query = parser::parse("π[id](users) times π[id](orders)", &relations).unwrap();
+        assert_eq!(query.evaluate().unwrap().data.tuples().len(), 6);
+
+        let query = Operator::Unary(UnaryOpr::Rename(
+            None,
+            vec![("id".to_string(), "name".to_string())],
+            Input::Relation(&users),
+        ));
+        assert_eq!(
+            query.evaluate().unwrap_err(),
+            Error::DuplicateAttribute("name".to_string())
+        );
+
+        let query = Operator::Unary(UnaryOpr::Rename(
+            None,
+            vec![("phone".to_string(), "mobile".to_string())],
+            Input::Relation(&users),
+        ));
+        assert_eq!(
+            query.evaluate().unwrap_err(),
+            Error::UnknownAttribute("phone".to_string())
+        );
+    }
+
+    #[test]
+    fn division() {
+        let attribute = |name: &str| Attribute {
+            name: name.to_string(),
+            atype: Type::Str,
+            nullable: false,
+        };
+        let str_row = |values: &[&str]| {
+            values
+                .iter()
+                .map(|v| Value::Str(v.to_string()))
+                .collect::<Row>()
+        };
+
+        let mut taken = Relation {
+            name: "taken".to_string(),
+            pk: None,
+            schema: Schema {
+                attributes: vec![attribute("student"), attribute("course")],
+            },
+            data: Data::NoPK((0, Box::default())),
+        };
+        taken
+            .insert_rows(vec![
+                str_row(&["ann", "db"]),
+                str_row(&["ann", "os"]),
+                str_row(&["ann", "ai"]),
+                str_row(&["bob", "db"]),
+                str_row(&["cat", "os"]),
+                str_row(&["cat", "db"]),
+                str_row(&["cat", "db"]),
+            ])
+            .unwrap();
+
+        let mut required = Relation {
+            name: "required".to_string(),
+            pk: Some(vec![0]),
+            schema: Schema {
+                attributes: vec![attribute("course")],
+            },
+            data: Data::WithPK(Box::default()),
+        };
+        required
+            .insert_rows(vec![str_row(&["db"]), str_row(&["os"])])
+            .unwrap();
+
+        // students who took every required course
+        let query = Operator::Binary(BinaryOpr::Division(
+            Input::Relation(&taken),
+            Input::Relation(&required),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert_eq!(result.schema.attributes, vec![attribute("student")]);
+        assert_eq!(
+            result.data.tuples(),
+            vec![str_row(&["ann"]), str_row(&["cat"])]
+        );
+
+        // nothing is required, every student qualifies
+        let mut none = required.clone();
+        none.data = Data::WithPK(Box::default());
+        let query = Operator::Binary(BinaryOpr::Division(
+            Input::Relation(&taken),
+            Input::Relation(&none),
+        ));
+        assert_eq!(query.evaluate().unwrap().data.tuples().len(), 3);
+
+        let mut grades = required.clone();
+        grades.schema.attributes = vec![attribute("grade")];
+        let query = Operator::Binary(BinaryOpr::Division(
+            Input::Relation(&taken),
+            Input::Relation(&grades),
+        ));
+        assert!(matches!(query.evaluate(), Err(Error::SchemaMismatch(_))));
+    }
+
+    #[test]
+    fn outer_joins() {
+        let (mut users, mut orders) = create_users_and_orders();
+        users
+            .insert_row(vec![Value::Int(3), Value::Str("carol".to_string())])
+            .unwrap();
+        orders
+            .insert_row(vec![
+                Value::Int(13),
+                Value::Int(9),
+                Value::Str("mug".to_string()),
+            ])
+            .unwrap();
+
+        let on = || {
+            SelPredicate::Condition(
+                (
+                    Attribute {
+                        name: "users.id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    Comp::EQ,
+                    Operand::Attr(Attribute {
+                        name: "user_id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    }),
+                ),
+                None,
+            )
+        };
+        let nullable = |relation: &Relation| {
+            relation
+                .schema
+                .attributes
+                .iter()
+                .map(|a| a.nullable)
+                .collect::<Vec<_>>()
+        };
+
+        // all users and their orders, if any
+        let query = Operator::Binary(BinaryOpr::LeftOuterJoin(
+            on(),
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert!(result.pk.is_none());
+        assert_eq!(nullable(&result), vec![false, false, true, true, true]);
+        assert_eq!(
+            result.data.tuples()[2..],
+            vec![
+                vec![
+                    Value::Int(2),
+                    Value::Str("alice".to_string()),
+                    Value::Int(12),
+                    Value::Int(2),
+                    Value::Str("pad".to_string()),
+                ],
+                vec![
+                    Value::Int(3),
+                    Value::Str("carol".to_string()),
+                    Value::Null,
+                    Value::Null,
+                    Value::Null,
+                ],
+            ]
+        );
+
+        // every order matches at most one user, so orders.id stays a key
+        let query = Operator::Binary(BinaryOpr::RightOuterJoin(
+            on(),
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert_eq!(result.pk, Some(vec![2]));
+        assert_eq!(nullable(&result), vec![true, true, false, false, false]);
+        assert_eq!(result.data.tuples().len(), 4);
+        assert_eq!(
+            result.data.tuples()[3],
+            vec![
+                Value::Null,
+                Value::Null,
+                Value::Int(13),
+                Value::Int(9),
+                Value::Str("mug".to_string()),
+            ]
+        );
+
+        let query = Operator::Binary(BinaryOpr::FullOuterJoin(
+            on(),
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert!(result.pk.is_none());
+        assert_eq!(nullable(&result), vec![true; 5]);
+        assert_eq!(result.data.tuples().len(), 5);
+
+        // NULLs never match, not even each other
+        let full = Operator::Unary(UnaryOpr::Rename(
+            None,
+            vec![("orders.id".to_string(), "order_id".to_string())],
+            Input::Relation(&result),
+        ))
+        .evaluate()
+        .unwrap();
+        let query = Operator::Binary(BinaryOpr::ThetaJoin(
+            SelPredicate::Condition(
+                (
+                    Attribute {
+                        name: "order_id".to_string(),
+                        atype: Type::Int,
+                        nullable: true,
+                    },
+                    Comp::EQ,
+                    Operand::Attr(Attribute {
+                        name: "o.id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    }),
+                ),
+                None,
+            ),
+            Input::Relation(&full),
+            Operator::Unary(UnaryOpr::Rename(
+                Some("o".to_string()),
+                vec![],
+                Input::Relation(&orders),
+            ))
+            .into(),
+        ));
+        assert_eq!(query.evaluate().unwrap().data.tuples().len(), 4);
+    }
+
+    #[test]
+    fn semi_and_anti_joins() {
+        let (mut users, orders) = create_users_and_orders();
+        users
+            .insert_row(vec![Value::Int(3), Value::Str("carol".to_string())])
+            .unwrap();
+
+        let on = |comp: Comp| {
+            SelPredicate::Condition(
+                (
+                    Attribute {
+                        name: "users.id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    comp,
+                    Operand::Attr(Attribute {
+                        name: "user_id".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    }),
+                ),
+                None,
+            )
+        };
+
+        // users with orders, each listed once however many orders they have
+        let query = Operator::Binary(BinaryOpr::SemiJoin(
+            on(Comp::EQ),
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert_eq!(result.pk, users.pk);
+        assert_eq!(result.schema.attributes, users.schema.attributes);
+        assert_eq!(
+            result.data.tuples(),
+            vec![
+                vec![Value::Int(1), Value::Str("bob".to_string())],
+                vec![Value::Int(2), Value::Str("alice".to_string())],
+            ]
+        );
+
+        // users without any order
+        let query = Operator::Binary(BinaryOpr::AntiJoin(
+            on(Comp::EQ),
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        assert_eq!(
+            query.evaluate().unwrap().data.tuples(),
+            vec![vec![Value::Int(3), Value::Str("carol".to_string())]]
+        );
+
+        // without an equality every pair is checked
+        let query = Operator::Binary(BinaryOpr::AntiJoin(
+            on(Comp::GT),
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        assert_eq!(
+            query.evaluate().unwrap().data.tuples(),
+            vec![vec![Value::Int(1), Value::Str("bob".to_string())]]
+        );
+    }
+
+    #[test]
+    fn aggregation() {
+        let (_, mut orders) = create_users_and_orders();
+        orders.schema.attributes.push(Attribute {
+            name: "qty".to_string(),
+            atype: Type::Int,
+            nullable: true,
+        });
+        orders.data = Data::WithPK(Box::default());
+        orders
+            .insert_rows(vec![
+                vec![
+                    Value::Int(10),
+                    Value::Int(1),
+                    Value::Str("pen".to_string()),
+                    Value::Int(3),
+                ],
+                vec![
+                    Value::Int(11),
+                    Value::Int(1),
+                    Value::Str("ink".to_string()),
+                    Value::Int(4),
+                ],
+                vec![
+                    Value::Int(12),
+                    Value::Int(2),
+                    Value::Str("pad".to_string()),
+                    Value::Null,
+                ],
+            ])
+            .unwrap();
+
+        // γ_{user_id; count, count(qty), sum(qty), min(item), max(item), avg(qty)}
+        let query = Operator::Unary(UnaryOpr::Aggregation(
+            vec!["user_id".to_string()],
+            vec![
+                Aggregate::Count(None),
+                Aggregate::Count(Some("qty".to_string())),
+                Aggregate::Sum("qty".to_string()),
+                Aggregate::Min("item".to_string()),
+                Aggregate::Max("item".to_string()),
+                Aggregate::Avg("qty".to_string()),
+            ],
+            Input::Relation(&orders),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert_eq!(result.pk, Some(vec![0]));
+        assert_eq!(
+            result
+                .schema
+                .attributes
+                .iter()
+                .map(|a| (a.name.as_str(), &a.atype))
+                .collect::<Vec<_>>(),
+            vec![
+                ("user_id", &Type::Int),
+                ("count", &Type::Int),
+                ("count(qty)", &Type::Int),
+                ("sum(qty)", &Type::Int),
+                ("min(item)", &Type::Str),
+                ("max(item)", &Type::Str),
+                ("avg(qty)", &Type::Float),
+            ]
+        );
+        assert_eq!(
+            result.data.tuples(),
+            vec![
+                vec![
+                    Value::Int(1),
+                    Value::Int(2),
+                    Value::Int(2),
+                    Value::Int(7),
+                    Value::Str("ink".to_string()),
+                    Value::Str("pen".to_string()),
+                    Value::Float(Float(3.5)),
+                ],
+                vec![
+                    Value::Int(2),
+                    Value::Int(1),
+                    Value::Int(0),
+                    Value::Null,
+                    Value::Str("pad".to_string()),
+                    Value::Str("pad".to_string()),
+                    Value::Null,
+                ],
+            ]
+        );
+
+        // a single group over the whole relation, even an empty one
+        let empty = create_test_relation();
+        let query = Operator::Unary(UnaryOpr::Aggregation(
+            vec![],
+            vec![Aggregate::Count(None), Aggregate::Max("key".to_string())],
+            Input::Relation(&empty),
+        ));
+        let result = query.evaluate().unwrap();
+        assert!(result.pk.is_none());
+        assert_eq!(result.data.tuples(), vec![vec![Value::Int(0), Value::Null]]);
+
+        let query = Operator::Unary(UnaryOpr::Aggregation(
+            vec![],
+            vec![Aggregate::Sum("value".to_string())],
+            Input::Relation(&empty),
+        ));
+        assert!(matches!(query.evaluate(), Err(Error::TypeMismatch(_))));
+    }
+
+    #[test]
+    fn sort_limit_offset() {
+        let (_, orders) = create_users_and_orders();
+
+        // τ_{user_id DESC, item}
+        let query = Operator::Unary(UnaryOpr::Sort(
+            vec![
+                SortKey::Desc("user_id".to_string()),
+                SortKey::Asc("item".to_string()),
+            ],
+            Input::Relation(&orders),
+        ));
+        let sorted = query.evaluate().unwrap();
+
+        let ids = |relation: &Relation| {
+            relation
+                .data
+                .tuples()
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sorted.pk, orders.pk);
+        assert_eq!(
+            ids(&sorted),
+            vec![Value::Int(12), Value::Int(11), Value::Int(10)]
+        );
+
+        // the order survives operators that do not reorder tuples
+        let query = Operator::Unary(UnaryOpr::Projection(
+            ProjAttrs::Attr(
+                Attribute {
+                    name: "item".to_string(),
+                    atype: Type::Str,
+                    nullable: false,
+                },
+                None,
+            ),
+            Input::Relation(&sorted),
+        ));
+        assert_eq!(
+            query.evaluate().unwrap().data.tuples(),
+            vec![
+                vec![Value::Str("pad".to_string())],
+                vec![Value::Str("ink".to_string())],
+                vec![Value::Str("pen".to_string())],
+            ]
+        );
+
+        let query = Operator::Unary(UnaryOpr::Offset(1, Input::Relation(&sorted)));
+        let offset = query.evaluate().unwrap();
+        assert_eq!(ids(&offset), vec![Value::Int(11), Value::Int(10)]);
+
+        let query = Operator::Unary(UnaryOpr::Limit(1, Input::Relation(&offset)));
+        assert_eq!(ids(&query.evaluate().unwrap()), vec![Value::Int(11)]);
+
+        let query = Operator::Unary(UnaryOpr::Limit(5, Input::Relation(&offset)));
+        assert_eq!(ids(&query.evaluate().unwrap()).len(), 2);
+
+        // NULLs last when ascending
+        let mut values = create_test_relation();
+        values.pk = None;
+        values.schema.attributes[0].nullable = true;
+        values.data = Data::NoPK((0, Box::default()));
+        values
+            .insert_rows(vec![
+                vec![Value::Null, Value::Str("a".to_string())],
+                vec![Value::Int(2), Value::Str("b".to_string())],
+                vec![Value::Int(1), Value::Str("c".to_string())],
+            ])
+            .unwrap();
+        let query = Operator::Unary(UnaryOpr::Sort(
+            vec![SortKey::Asc("key".to_string())],
+            Input::Relation(&values),
+        ));
+        assert_eq!(
+            ids(&query.evaluate().unwrap()),
+            vec![Value::Int(1), Value::Int(2), Value::Null]
+        );
+
+        let query = Operator::Unary(UnaryOpr::Sort(
+            vec![SortKey::Asc("phone".to_string())],
+            Input::Relation(&values),
+        ));
+        assert!(matches!(query.evaluate(), Err(Error::UnknownAttribute(_))));
+    }
+
+    #[test]
+    fn operator_trees() {
+        let (users, orders) = create_users_and_orders();
+
+        // σ_{name = bob}(π_{name, item}(users ⋈_{users.id = orders.user_id} orders)),
+        // built once
+        let on = SelPredicate::Condition(
+            (
+                Attribute {
+                    name: "users.id".to_string(),
+                    atype: Type::Int,
+                    nullable: false,
+                },
+                Comp::EQ,
+                Operand::Attr(Attribute {
+                    name: "orders.user_id".to_string(),
+                    atype: Type::Int,
+                    nullable: false,
+                }),
+            ),
+            None,
+        );
+        let join = Operator::Binary(BinaryOpr::ThetaJoin(
+            on,
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        let projection = Operator::Unary(UnaryOpr::Projection(
+            ProjAttrs::Attr(
+                Attribute {
+                    name: "name".to_string(),
+                    atype: Type::Str,
+                    nullable: false,
+                },
+                Some(Box::new(ProjAttrs::Attr(
+                    Attribute {
+                        name: "item".to_string(),
+                        atype: Type::Str,
+                        nullable: false,
+                    },
+                    None,
+                ))),
+            ),
+            join.into(),
+        ));
+        let query = Operator::Unary(UnaryOpr::Selection(
+            SelPredicate::Condition(
+                (
+                    Attribute {
+                        name: "name".to_string(),
+                        atype: Type::Str,
+                        nullable: false,
+                    },
+                    Comp::EQ,
+                    Operand::Value(Value::Str("bob".to_string())),
+                ),
+                None,
+            ),
+            Input::Operator(Box::new(projection)),
+        ));
+
+        let result = query.evaluate().unwrap();
+        let mut tuples = result.data.tuples();
+        tuples.sort();
+        assert_eq!(
+            tuples,
+            vec![
+                vec![Value::Str("bob".to_string()), Value::Str("ink".to_string())],
+                vec![Value::Str("bob".to_string()), Value::Str("pen".to_string())],
+            ]
+        );
+
+        // the same tree evaluates again to the same result
+        assert_eq!(query.evaluate().unwrap().data.tuples().len(), 2);
+
+        // errors deep in the tree surface at the root
+        let query = Operator::Binary(BinaryOpr::Union(
+            Operator::Unary(UnaryOpr::Sort(
+                vec![SortKey::Asc("phone".to_string())],
+                Input::Relation(&users),
+            ))
+            .into(),
+            Input::Relation(&users),
+        ));
+        assert!(matches!(query.evaluate(), Err(Error::UnknownAttribute(_))));
+    }
+
+    #[test]
+    fn insert_errors() {
+        let mut relation = create_test_relation();
+
+        assert!(matches!(
+            relation.insert_row(vec![Value::Int(1)]),
+            Err(Error::SchemaMismatch(_))
+        ));
+        assert!(matches!(
+            relation.insert_row(vec![
+                Value::Str("1".to_string()),
+                Value::Str("foo".to_string())
+            ]),
+            Err(Error::TypeMismatch(_))
+        ));
+
+        // nothing is inserted when one of the rows fails
+        assert_eq!(
+            relation.insert_rows(vec![
+                vec![Value::Int(1), Value::Str("foo".to_string())],
+                vec![Value::Int(1), Value::Str("bar".to_string())],
+            ]),
+            Err(Error::DuplicatePrimaryKey("[Int(1)]".to_string()))
+        );
+        assert!(relation.data.tuples().is_empty());
+
+        assert!(matches!(
+            relation
+                .data
+                .insert(None, vec![Value::Int(1), Value::Str("foo".to_string())]),
+            Err(Error::MissingKey(_))
+        ));
+        assert!(matches!(
+            relation.data.contains(None, None),
+            Err(Error::MissingKey(_))
+        ));
+        assert_eq!(
+            relation.data.contains(Some(vec![Value::Int(1)]), None),
+            Ok(false)
+        );
+
+        let err = relation
+            .insert_row(vec![Value::Int(1), Value::Str("foo".to_string())])
+            .and_then(|_| relation.insert_row(vec![Value::Int(1), Value::Str("foo".to_string())]))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "duplicate primary key [Int(1)]");
+    }
+
+    #[test]
+    fn nulls() {
+        let mut contacts = Relation {
+            name: "contacts".to_string(),
+            pk: Some(vec![0]),
+            schema: Schema {
+                attributes: vec![
+                    Attribute {
+                        name: "name".to_string(),
+                        atype: Type::Str,
+                        nullable: true,
+                    },
+                    Attribute {
+                        name: "phone".to_string(),
+                        atype: Type::Int,
+                        nullable: true,
+                    },
+                    Attribute {
+                        name: "city".to_string(),
+                        atype: Type::Str,
+                        nullable: false,
+                    },
+                ],
+            },
+            data: Data::WithPK(Box::default()),
+        };
+        let str = |s: &str| Value::Str(s.to_string());
+
+        contacts
+            .insert_rows(vec![
+                vec![str("bob"), Value::Int(9999999999), str("pune")],
+                vec![str("alice"), Value::Null, str("goa")],
+                vec![str("carol"), Value::Null, str("pune")],
+            ])
+            .unwrap();
+        assert!(matches!(
+            contacts.insert_row(vec![str("dave"), Value::Int(1), Value::Null]),
+            Err(Error::TypeMismatch(_))
+        ));
+        assert_eq!(
+            contacts.insert_row(vec![Value::Null, Value::Int(1), str("goa")]),
+            Err(Error::NullPrimaryKey("name".to_string()))
+        );
+
+        let condition = |name: &str, atype: Type, comp: Comp, value: Value, next| {
+            SelPredicate::Condition(
+                (
+                    Attribute {
+                        name: name.to_string(),
+                        atype,
+                        nullable: true,
+                    },
+                    comp,
+                    Operand::Value(value),
+                ),
+                next,
+            )
+        };
+        let names = |predicate: SelPredicate| {
+            let query = Operator::Unary(UnaryOpr::Selection(predicate, Input::Relation(&contacts)));
+            let mut names = query
+                .evaluate()
+                .unwrap()
+                .data
+                .tuples()
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        // phone IS NULL, phone IS NOT NULL
+        assert_eq!(
+            names(condition(
+                "phone",
+                Type::Int,
+                Comp::IsNull,
+                Value::Null,
+                None
+            )),
+            vec![str("alice"), str("carol")]
+        );
+        assert_eq!(
+            names(condition(
+                "phone",
+                Type::Int,
+                Comp::IsNotNull,
+                Value::Null,
+                None
+            )),
+            vec![str("bob")]
+        );
+
+        // phone <> 1 is unknown for a NULL phone, and so is phone = NULL for
+        // every row
+        assert_eq!(
+            names(condition("phone", Type::Int, Comp::NE, Value::Int(1), None)),
+            vec![str("bob")]
+        );
+        assert!(names(condition("phone", Type::Int, Comp::EQ, Value::Null, None)).is_empty());
+
+        // unknown OR true is true
+        assert_eq!(
+            names(condition(
+                "phone",
+                Type::Int,
+                Comp::NE,
+                Value::Int(1),
+                Some((
+                    Connective::OR,
+                    Box::new(condition("city", Type::Str, Comp::EQ, str("goa"), None))
+                )),
+            )),
+            vec![str("alice"), str("bob")]
+        );
+
+        assert_eq!(Truth::Unknown.and(Truth::False), Truth::False);
+        assert_eq!(Truth::Unknown.and(Truth::True), Truth::Unknown);
+        assert_eq!(Truth::Unknown.or(Truth::False), Truth::Unknown);
+        assert_eq!(!Truth::Unknown, Truth::Unknown);
+        assert_eq!(
+            condition("phone", Type::Int, Comp::GT, Value::Int(1), None).truth(
+                &contacts.schema,
+                &vec![str("alice"), Value::Null, str("goa")]
+            ),
+            Ok(Truth::Unknown)
+        );
+    }
+
+    #[test]
+    fn scalar_types() {
+        let attr = |name: &str, atype: Type| Attribute {
+            name: name.to_string(),
+            atype,
+            nullable: false,
+        };
+        let mut readings = Relation {
+            name: "readings".to_string(),
+            pk: Some(vec![0]),
+            schema: Schema {
+                attributes: vec![
+                    attr("value", Type::Float),
+                    attr("valid", Type::Bool),
+                    attr("day", Type::Date),
+                    attr("created_at", Type::Timestamp),
+                    attr("raw", Type::Bytes),
+                ],
+            },
+            data: Data::WithPK(Box::default()),
+        };
+        let day = Date::from_ymd(2024, 2, 29).unwrap();
+        let row = |value: f64, valid: bool, hour: u32| {
+            vec![
+                Value::Float(Float(value)),
+                Value::Bool(valid),
+                Value::Date(day),
+                Value::Timestamp(Timestamp::new(day, hour, 30, 0, 0).unwrap()),
+                Value::Bytes(vec![hour as u8]),
+            ]
+        };
+
+        readings
+            .insert_rows(vec![
+                row(f64::NAN, false, 3),
+                row(1.5, true, 1),
+                row(-0.0, true, 2),
+            ])
+            .unwrap();
+        assert!(matches!(
+            readings.insert_row(row(1.5, false, 4)),
+            Err(Error::DuplicatePrimaryKey(_))
+        ));
+        // -0.0 and 0.0 are the same key
+        assert!(matches!(
+            readings.insert_row(row(0.0, true, 0)),
+            Err(Error::DuplicatePrimaryKey(_))
+        ));
+        assert!(matches!(
+            readings.insert_row(vec![Value::Int(1), Value::Bool(true)]),
+            Err(Error::SchemaMismatch(_))
+        ));
+        assert!(!readings.schema.validate_row(&vec![
+            Value::Float(Float(2.0)),
+            Value::Int(1),
+            Value::Date(day),
+            Value::Timestamp(Timestamp(0)),
+            Value::Bytes(vec![]),
+        ]));
+
+        // floats keep a total order as keys, NaN last
+        let values = readings
+            .data
+            .tuples()
+            .into_iter()
+            .map(|row| row[0].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values[..2],
+            [Value::Float(Float(0.0)), Value::Float(Float(1.5))]
+        );
+        assert!(matches!(values[2], Value::Float(Float(f)) if f.is_nan()));
+
+        // a stored -0.0 is equal to 0.0 either way it is written
+        let relations = [&readings];
+        for src in ["σ[value = 0.0](readings)", "σ[value = -0.0](readings)"] {
+            let query = parser::parse(src, &relations).unwrap();
+            assert_eq!(query.evaluate().unwrap().data.tuples().len(), 1);
+        }
+
+        let query = Operator::Unary(UnaryOpr::Sort(
+            vec![SortKey::Desc("created_at".to_string())],
+            Input::Relation(&readings),
+        ));
+        let latest = &query.evaluate().unwrap().data.tuples()[0];
+        assert_eq!(latest[4], Value::Bytes(vec![3]));
+
+        assert_eq!(Date::from_ymd(1970, 1, 1), Some(Date(0)));
+        assert_eq!(Date::from_ymd(1969, 12, 31), Some(Date(-1)));
+        assert_eq!(Date::from_ymd(2023, 2, 29), None);
+        assert_eq!(Date::from_ymd(2024, 13, 1), None);
+        assert_eq!(day.to_string(), "2024-02-29");
+        assert_eq!(Date::from_ymd(1600, 3, 1).unwrap().ymd(), (1600, 3, 1));
+        assert_eq!(
+            Timestamp::new(day, 13, 5, 9, 250).unwrap().to_string(),
+            "2024-02-29 13:05:09.000250"
+        );
+        assert_eq!(Timestamp(-1).to_string(), "1969-12-31 23:59:59.999999");
+
+        // out of range, rather than wrapping around or overflowing
+        assert!("4294967295-01-01".parse::<Date>().is_err());
+        assert!("2147483648-01-01".parse::<Date>().is_err());
+        let far = Date::from_ymd(999_999, 1, 1).unwrap();
+        assert_eq!(Timestamp::new(far, 0, 0, 0, 0), None);
+        assert!("999999-01-01 00:00:00".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn decimals() {
+        let dec = |s: &str| s.parse::<Decimal>().unwrap();
+        let mut prices = Relation {
+            name: "prices".to_string(),
+            pk: Some(vec![0]),
+            schema: Schema {
+                attributes: vec![
+                    Attribute {
+                        name: "price".to_string(),
+                        atype: Type::Decimal(5, 2),
+                        nullable: false,
+                    },
+                    Attribute {
+                        name: "item".to_string(),
+                        atype: Type::Str,
+                        nullable: false,
+                    },
+                ],
+            },
+            data: Data::WithPK(Box::default()),
+        };
+        let row = |price: &str, item: &str| {
+            vec![Value::Decimal(dec(price)), Value::Str(item.to_string())]
+        };
+
+        prices
+            .insert_rows(vec![
+                row("1.5", "pen"),
+                row("999.99", "desk"),
+                row("-0.25", "coupon"),
+            ])
+            .unwrap();
+
+        // 1.50 is the same key as 1.5
+        assert!(matches!(
+            prices.insert_row(row("1.50", "ink")),
+            Err(Error::DuplicatePrimaryKey(_))
+        ));
+        assert!(matches!(
+            prices.insert_row(row("1000", "sofa")),
+            Err(Error::Overflow(_))
+        ));
+        assert!(matches!(
+            prices.insert_row(row("0.125", "clip")),
+            Err(Error::Overflow(_))
+        ));
+        assert!(matches!(
+            prices.insert_row(vec![
+                Value::Float(Float(2.5)),
+                Value::Str("pad".to_string())
+            ]),
+            Err(Error::TypeMismatch(_))
+        ));
+
+        assert_eq!(
+            prices
+                .data
+                .tuples()
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>(),
+            vec![
+                Value::Decimal(dec("-0.25")),
+                Value::Decimal(dec("1.5")),
+                Value::Decimal(dec("999.99"))
+            ]
+        );
+
+        let query = Operator::Unary(UnaryOpr::Aggregation(
+            vec![],
+            vec![Aggregate::Sum("price".to_string())],
+            Input::Relation(&prices),
+        ));
+        let result = query.evaluate().unwrap();
+        assert_eq!(
+            result.schema.attributes[0].atype,
+            Type::Decimal(Decimal::MAX_PRECISION, 2)
+        );
+        assert_eq!(
+            result.data.tuples(),
+            vec![vec![Value::Decimal(dec("1001.24"))]]
+        );
+
+        let query = Operator::Unary(UnaryOpr::Aggregation(
+            vec![],
+            vec![Aggregate::Avg("price".to_string())],
+            Input::Relation(&prices),
+        ));
+        let result = query.evaluate().unwrap();
+        assert_eq!(
+            result.schema.attributes[0].atype,
+            Type::Decimal(Decimal::MAX_PRECISION, Decimal::DIVISION_SCALE)
+        );
+        assert!(matches!(
+            &result.data.tuples()[0][0],
+            Value::Decimal(d) if d.to_string() == "333.746667"
+        ));
+
+        let quotient = Expr::Binary(
+            Box::new(Expr::Attr("price".to_string())),
+            BinOp::Div,
+            Box::new(Expr::Value(Value::Int(3))),
+        );
+        assert_eq!(
+            quotient.infer_type(&prices.schema).unwrap(),
+            Type::Decimal(Decimal::MAX_PRECISION, Decimal::DIVISION_SCALE)
+        );
+        assert!(matches!(
+            quotient.eval(&prices.schema, &prices.data.tuples()[1]),
+            Ok(Value::Decimal(d)) if d.to_string() == "0.500000"
+        ));
+
+        assert_eq!(dec("0.1").checked_add(dec("0.2")), Some(dec("0.3")));
+        assert_eq!(
+            dec("1.25").checked_sub(dec("2")).unwrap().to_string(),
+            "-0.75"
+        );
+        assert_eq!(
+            dec("1.5").checked_mul(dec("-0.20")).unwrap().to_string(),
+            "-0.300"
+        );
+        assert_eq!(
+            dec("10.00").checked_div(dec("3")).unwrap().to_string(),
+            "3.333333"
+        );
+        assert_eq!(
+            dec("-2").checked_div(dec("3")).unwrap().to_string(),
+            "-0.666667"
+        );
+        assert_eq!(
+            dec("1.23456789")
+                .checked_div(dec("0.5"))
+                .unwrap()
+                .to_string(),
+            "2.46913578"
+        );
+        assert_eq!(dec("1").checked_div(dec("0")), None);
+        assert!(dec("10.01") > dec("10.001"));
+        assert!(Decimal::new(i128::MAX, 0) > dec("1.5"));
+        assert!(Decimal::new(i128::MIN, 0) < dec("-1.5"));
+        assert_eq!(Decimal::new(i128::MAX, 0).checked_add(dec("1")), None);
+        assert_eq!(dec("-.5").to_string(), "-0.5");
+        assert_eq!(dec("0.05").to_string(), "0.05");
+        assert!(matches!(
+            "1.2.3".parse::<Decimal>(),
+            Err(Error::TypeMismatch(_))
+        ));
+        assert!(matches!(
+            "-".parse::<Decimal>(),
+            Err(Error::TypeMismatch(_))
+        ));
+        assert!(matches!(
+            "1".repeat(40).parse::<Decimal>(),
+            Err(Error::Overflow(_))
+        ));
+
+        // DECIMALs of different precisions are of the same kind
+        let relation = |name: &str, precision: u32, rows: &[(i64, &str)]| {
+            let mut relation = Relation::builder(name)
+                .column("x", Type::Int)
+                .column("y", Type::Decimal(precision, 2))
+                .build()
+                .unwrap();
+            relation
+                .insert_rows(
+                    rows.iter()
+                        .map(|(x, y)| vec![Value::Int(*x), Value::Decimal(dec(y))])
+                        .collect(),
+                )
+                .unwrap();
+            relation
+        };
+        let c = relation("c", 3, &[(1, "1.50"), (2, "2.25"), (1, "2.25")]);
+        let e = relation("e", 6, &[(3, "2.25"), (1, "1.5"), (4, "1000.00")]);
+        let relations = [&c, &e];
+        let run = |src: &str| parser::parse(src, &relations).unwrap().evaluate().unwrap();
+
+        let union = run("c ∪ e");
+        assert_eq!(union.schema.attributes[1].atype, Type::Decimal(6, 2));
+        assert_eq!(union.data.tuples().len(), 5);
+        assert_eq!(
+            run("c ÷ π[y](σ[x < 4](e))").data.tuples(),
+            vec![vec![Value::Int(1)]]
+        );
+        assert_eq!(
+            run("c ⋈ e").data.tuples(),
+            vec![vec![Value::Int(1), Value::Decimal(dec("1.50"))]]
+        );
+
+        let query = Operator::Unary(UnaryOpr::Selection(
+            SelPredicate::Condition(
+                (
+                    Attribute {
+                        name: "y".to_string(),
+                        atype: Type::Decimal(6, 2),
+                        nullable: false,
+                    },
+                    Comp::EQ,
+                    Operand::Value(Value::Decimal(dec("2.250"))),
+                ),
+                None,
+            ),
+            Input::Relation(&c),
+        ));
+        assert_eq!(query.evaluate().unwrap().data.tuples().len(), 2);
+    }
+
+    fn create_lines() -> Relation {
+        let attr = |name: &str, atype: Type| Attribute {
+            name: name.to_string(),
+            atype,
+            nullable: false,
+        };
+        let mut lines = Relation {
+            name: "lines".to_string(),
+            pk: Some(vec![0]),
+            schema: Schema {
+                attributes: vec![
+                    attr("id", Type::Int),
+                    attr("item", Type::Str),
+                    attr("price", Type::Decimal(6, 2)),
+                    attr("qty", Type::Int),
+                    attr("start_date", Type::Date),
+                    attr("end_date", Type::Date),
+                ],
+            },
+            data: Data::WithPK(Box::default()),
+        };
+        let row = |id: i64, item: &str, price: &str, qty: i64, start: u32, end: u32| {
+            vec![
+                Value::Int(id),
+                Value::Str(item.to_string()),
+                Value::Decimal(price.parse().unwrap()),
+                Value::Int(qty),
+                Value::Date(Date::from_ymd(2024, 1, start).unwrap()),
+                Value::Date(Date::from_ymd(2024, 1, end).unwrap()),
+            ]
+        };
+        lines
+            .insert_rows(vec![
+                row(1, "pen", "2.50", 10, 1, 5),
+                row(2, "desk", "120.00", 1, 9, 3),
+                row(3, "ink", "9.99", 11, 2, 2),
+            ])
+            .unwrap();
+
+        lines
+    }
+
+    #[test]
+    fn expressions() {
+        let lines = create_lines();
+        let attr = |name: &str| Box::new(Expr::Attr(name.to_string()));
+        let value = |v: Value| Box::new(Expr::Value(v));
+        let ids = |predicate: SelPredicate| {
+            let query = Operator::Unary(UnaryOpr::Selection(predicate, Input::Relation(&lines)));
+            query.evaluate().map(|r| {
+                r.data
+                    .tuples()
+                    .into_iter()
+                    .map(|row| row[0].clone())
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        // price * qty > 100
+        let total = Expr::Binary(attr("price"), BinOp::Mul, attr("qty"));
+        let expensive = Expr::Compare(Box::new(total.clone()), Comp::GT, value(Value::Int(100)));
+        assert_eq!(
+            ids(SelPredicate::Expr(expensive, None)),
+            Ok(vec![Value::Int(2), Value::Int(3)])
+        );
+        assert_eq!(
+            total.infer_type(&lines.schema),
+            Ok(Type::Decimal(Decimal::MAX_PRECISION, 2))
+        );
+
+        // start_date < end_date OR upper(item) = 'INK'
+        let predicate = SelPredicate::Expr(
+            Expr::Compare(attr("start_date"), Comp::LT, attr("end_date")),
+            Some((
+                Connective::OR,
+                Box::new(SelPredicate::Expr(
+                    Expr::Compare(
+                        Box::new(Expr::Call(
+                            Function::Upper,
+                            vec![Expr::Attr("item".to_string())],
+                        )),
+                        Comp::EQ,
+                        value(Value::Str("INK".to_string())),
+                    ),
+                    None,
+                )),
+            )),
+        );
+        assert_eq!(ids(predicate), Ok(vec![Value::Int(1), Value::Int(3)]));
+
+        // the expression form of an equality still joins on a hash
+        let (users, orders) = create_users_and_orders();
+        let query = Operator::Binary(BinaryOpr::ThetaJoin(
+            SelPredicate::Expr(
+                Expr::Compare(attr("users.id"), Comp::EQ, attr("user_id")),
+                None,
+            ),
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        assert_eq!(query.evaluate().unwrap().data.tuples().len(), 3);
+
+        // errors, when checking types and when evaluating
+        let concat = Expr::Binary(attr("item"), BinOp::Concat, attr("qty"));
+        assert!(matches!(
+            ids(SelPredicate::Expr(concat, None)),
+            Err(Error::TypeMismatch(_))
+        ));
+        assert!(matches!(
+            ids(SelPredicate::Expr(
+                Expr::Binary(attr("qty"), BinOp::Add, attr("qty")),
+                None
+            )),
+            Err(Error::TypeMismatch(_))
+        ));
+        let ratio = Expr::Binary(
+            attr("qty"),
+            BinOp::Div,
+            Box::new(Expr::Binary(attr("qty"), BinOp::Sub, attr("qty"))),
+        );
+        assert_eq!(
+            ids(SelPredicate::Expr(
+                Expr::Compare(Box::new(ratio), Comp::GT, value(Value::Int(1))),
+                None
+            )),
+            Err(Error::DivisionByZero)
+        );
+
+        let row = &lines.data.tuples()[0];
+        let accepts = |expr: Expr| SelPredicate::Expr(expr, None).evaluate(&lines.schema, row);
+        assert_eq!(
+            accepts(Expr::Compare(attr("qty"), Comp::GT, value(Value::Int(1)))),
+            Ok(true)
+        );
+        assert_eq!(
+            accepts(Expr::Compare(
+                Box::new(Expr::Binary(attr("qty"), BinOp::Div, value(Value::Int(0)))),
+                Comp::GT,
+                value(Value::Int(1))
+            )),
+            Err(Error::DivisionByZero)
+        );
+
+        let eval = |expr: Expr| expr.eval(&lines.schema, row).unwrap();
+        assert_eq!(
+            eval(Expr::Binary(
+                attr("item"),
+                BinOp::Concat,
+                value(Value::Str("s".to_string()))
+            )),
+            Value::Str("pens".to_string())
+        );
+        assert_eq!(
+            eval(Expr::Binary(attr("qty"), BinOp::Mod, value(Value::Int(4)))),
+            Value::Int(2)
+        );
+        assert_eq!(
+            eval(Expr::Binary(
+                value(Value::Null),
+                BinOp::Div,
+                value(Value::Int(0))
+            )),
+            Value::Null
+        );
+        assert_eq!(
+            eval(Expr::Binary(
+                attr("qty"),
+                BinOp::Div,
+                value(Value::Float(Float(4.0)))
+            )),
+            Value::Float(Float(2.5))
+        );
+        assert_eq!(
+            eval(Expr::Neg(Box::new(Expr::Call(
+                Function::Abs,
+                vec![Expr::Attr("price".to_string())]
+            )))),
+            Value::Decimal("-2.5".parse().unwrap())
+        );
+        assert_eq!(
+            eval(Expr::Call(
+                Function::Coalesce,
+                vec![
+                    Expr::Value(Value::Null),
+                    Expr::Call(Function::Length, vec![Expr::Attr("item".to_string())])
+                ]
+            )),
+            Value::Int(3)
+        );
+        assert_eq!(
+            eval(Expr::Compare(value(Value::Null), Comp::EQ, attr("qty"))),
+            Value::Null
+        );
+        assert_eq!(
+            Expr::Value(Value::Null).infer_type(&lines.schema),
+            Err(Error::TypeMismatch(
+                "the type of Value(Null) is unknown, it is always NULL".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn predicate_trees() {
+        let lines = create_lines();
+        let compare = |name: &str, comp: Comp, value: Value| {
+            Box::new(SelPredicate::Expr(
+                Expr::Compare(
+                    Box::new(Expr::Attr(name.to_string())),
+                    comp,
+                    Box::new(Expr::Value(value)),
+                ),
+                None,
+            ))
+        };
+        let ids = |predicate: SelPredicate| {
+            let query = Operator::Unary(UnaryOpr::Selection(predicate, Input::Relation(&lines)));
+            query
+                .evaluate()
+                .unwrap()
+                .data
+                .tuples()
+                .into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()
+        };
+
+        // (qty > 5 OR item = desk) AND NOT price > 100
+        let grouped = SelPredicate::And(
+            Box::new(SelPredicate::Or(
+                compare("qty", Comp::GT, Value::Int(5)),
+                compare("item", Comp::EQ, Value::Str("desk".to_string())),
+            )),
+            Box::new(SelPredicate::Not(compare(
+                "price",
+                Comp::GT,
+                Value::Int(100),
+            ))),
+        );
+        assert_eq!(ids(grouped), vec![Value::Int(1), Value::Int(3)]);
+
+        // a node in the linked form is one parenthesized condition,
+        // item = desk OR qty > 10 AND NOT (item = pen)
+        let linked = SelPredicate::Expr(
+            Expr::Compare(
+                Box::new(Expr::Attr("item".to_string())),
+                Comp::EQ,
+                Box::new(Expr::Value(Value::Str("desk".to_string()))),
+            ),
+            Some((
+                Connective::OR,
+                Box::new(SelPredicate::Expr(
+                    Expr::Compare(
+                        Box::new(Expr::Attr("qty".to_string())),
+                        Comp::GT,
+                        Box::new(Expr::Value(Value::Int(10))),
+                    ),
+                    Some((
+                        Connective::AND,
+                        Box::new(SelPredicate::Not(compare(
+                            "item",
+                            Comp::EQ,
+                            Value::Str("pen".to_string()),
+                        ))),
+                    )),
+                )),
+            )),
+        );
+        assert_eq!(ids(linked), vec![Value::Int(2), Value::Int(3)]);
+
+        // NOT of unknown is still unknown
+        let unknown = SelPredicate::Not(compare("qty", Comp::EQ, Value::Null));
+        assert!(ids(unknown).is_empty());
+
+        // the equality in a tree is used for the join, the rest checked on
+        // each pair
+        let (users, orders) = create_users_and_orders();
+        let on = SelPredicate::And(
+            Box::new(SelPredicate::Expr(
+                Expr::Compare(
+                    Box::new(Expr::Attr("users.id".to_string())),
+                    Comp::EQ,
+                    Box::new(Expr::Attr("user_id".to_string())),
+                ),
+                None,
+            )),
+            compare("item", Comp::NE, Value::Str("pen".to_string())),
+        );
+        let query = Operator::Binary(BinaryOpr::ThetaJoin(
+            on,
+            Input::Relation(&users),
+            Input::Relation(&orders),
+        ));
+        assert_eq!(query.evaluate().unwrap().data.tuples().len(), 2);
+
+        let invalid = SelPredicate::Or(
+            compare("qty", Comp::GT, Value::Int(5)),
+            compare("phone", Comp::GT, Value::Int(5)),
+        );
+        let query = Operator::Unary(UnaryOpr::Selection(invalid, Input::Relation(&lines)));
+        assert!(matches!(query.evaluate(), Err(Error::UnknownAttribute(_))));
+    }
+
+    #[test]
+    fn extended_projection() {
+        let lines = create_lines();
+        let attr = |name: &str| Box::new(Expr::Attr(name.to_string()));
+
+        // π[id, item, price * qty AS total], with the attribute types given
+        // wrong, since only the names matter
+        let query = Operator::Unary(UnaryOpr::Projection(
+            ProjAttrs::Attr(
+                Attribute {
+                    name: "id".to_string(),
+                    atype: Type::Str,
+                    nullable: true,
+                },
+                Some(Box::new(ProjAttrs::Attr(
+                    Attribute {
+                        name: "item".to_string(),
+                        atype: Type::Int,
+                        nullable: false,
+                    },
+                    Some(Box::new(ProjAttrs::Expr(
+                        Expr::Binary(attr("price"), BinOp::Mul, attr("qty")),
+                        "total".to_string(),
+                        None,
+                    ))),
+                ))),
+            ),
+            Input::Relation(&lines),
+        ));
+        let result = query.evaluate().unwrap();
+
+        assert_eq!(result.pk, Some(vec![0]));
+        assert_eq!(
+            result.schema.attributes,
+            vec![
+                Attribute {
+                    name: "id".to_string(),
+                    atype: Type::Int,
+                    nullable: false,
+                },
+                Attribute {
+                    name: "item".to_string(),
+                    atype: Type::Str,
+                    nullable: false,
+                },
+                Attribute {
+                    name: "total".to_string(),
+                    atype: Type::Decimal(Decimal::MAX_PRECISION, 2),
+                    nullable: false,
+                },
+            ]
+        );
+        assert_eq!(
+            result.data.tuples()[0],
+            vec![
+                Value::Int(1),
+                Value::Str("pen".to_string()),
+                Value::Decimal("25.00".parse().unwrap()),
+            ]
+        );
+
+        // computed columns alone, duplicates removed
+        let query = Operator::Unary(UnaryOpr::Projection(
+            ProjAttrs::Expr(
+                Expr::Compare(attr("qty"), Comp::GT, Box::new(Expr::Value(Value::Int(5)))),
+                "bulk".to_string(),
+                None,
+            ),
+            Input::Relation(&lines),
+        ));
+        let result = query.evaluate().unwrap();
+        assert!(result.pk.is_none());
+        assert_eq!(result.schema.attributes[0].atype, Type::Bool);
+        assert_eq!(
+            result.data.tuples(),
+            vec![vec![Value::Bool(true)], vec![Value::Bool(false)]]
+        );
+
+        let query = Operator::Unary(UnaryOpr::Projection(
+            ProjAttrs::Expr(Expr::Value(Value::Null), "nothing".to_string(), None),
+            Input::Relation(&lines),
+        ));
+        assert!(matches!(query.evaluate(), Err(Error::TypeMismatch(_))));
+
+        let query = Operator::Unary(UnaryOpr::Projection(
+            ProjAttrs::Expr(
+                Expr::Call(Function::Lower, vec![Expr::Attr("item".to_string())]),
+                "item".to_string(),
+                Some(Box::new(ProjAttrs::Expr(
+                    Expr::Attr("item".to_string()),
+                    "item".to_string(),
+                    None,
+                ))),
+            ),
+            Input::Relation(&lines),
+        ));
+        assert_eq!(
+            query.evaluate().unwrap_err(),
+            Error::DuplicateAttribute("item".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_algebra() {
+        let (users, orders) = create_users_and_orders();
+        let lines = create_lines();
+        let relations = [&users, &orders, &lines];
+        let run = |src: &str| {
+            parser::parse(src, &relations)
+                .and_then(|query| query.evaluate())
+                .map(|r| r.data.tuples())
+        };
+        let str = |s: &str| Value::Str(s.to_string());
+
+        // symbols and keywords mean the same
+        assert_eq!(
+            run("π[name](σ[id ≥ 2](users))"),
+            run("PROJECT[name](select[id >= 2](users))")
+        );
+        assert_eq!(
+            run("π[name](σ[id ≥ 2](users))"),
+            Ok(vec![vec![str("alice")]])
+        );
+        assert_eq!(run("users").unwrap().len(), 2);
+
+        assert_eq!(
+            run("π[orders.item](users ⋈[users.id = orders.user_id ∧ users.name = 'bob'] orders)"),
+            Ok(vec![vec![str("pen")], vec![str("ink")]])
+        );
+        assert_eq!(
+            run("π[id](users) ∪ π[user_id](orders) − π[id](σ[name = 'bob'](users))"),
+            Ok(vec![vec![Value::Int(2)]])
+        );
+        assert_eq!(
+            run(
+                "π[item, price * qty AS total](σ[end_date >= start_date and not (qty < 10)](lines))"
+            ),
+            Ok(vec![
+                vec![str("pen"), Value::Decimal("25.00".parse().unwrap())],
+                vec![str("ink"), Value::Decimal("109.89".parse().unwrap())],
+            ])
+        );
+        assert_eq!(
+            run("π[id](σ[start_date = date '2024-01-02' or upper(item) = 'DESK'](lines))"),
+            Ok(vec![vec![Value::Int(2)], vec![Value::Int(3)]])
+        );
+        assert_eq!(
+            run("π[item](limit[2](τ[price desc](lines)))"),
+            Ok(vec![vec![str("desk")], vec![str("ink")]])
+        );
+        assert_eq!(
+            run("γ[; count(*), sum(qty)](lines)"),
+            Ok(vec![vec![Value::Int(3), Value::Int(22)]])
+        );
+        assert_eq!(
+            run("π[person](ρ[u; name -> person](users))"),
+            Ok(vec![vec![str("bob")], vec![str("alice")]])
+        );
+
+        // an operator on a relation takes names qualified by the relation
+        assert_eq!(
+            run("π[users.name](σ[users.id ≥ 2](users))"),
+            Ok(vec![vec![str("alice")]])
+        );
+        assert_eq!(
+            run("γ[lines.item; sum(lines.qty)](τ[lines.id desc](lines))").map(|t| t.len()),
+            Ok(3)
+        );
+        assert_eq!(
+            run("σ[orders.id > 1](users)"),
+            Err(Error::UnknownAttribute("orders.id".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let (users, _) = create_users_and_orders();
+        let parse = |src: &str| parser::parse(src, &[&users]).map(|_| ()).unwrap_err();
+
+        assert_eq!(
+            parse("π[name](\n  people)"),
+            Error::Parse {
+                line: 2,
+                column: 3,
+                message: "unknown relation people".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("σ[id >](users)"),
+            Error::Parse {
+                line: 1,
+                column: 7,
+                message: "expected an expression, found `]`".to_string(),
+            }
+        );
+        assert_eq!(
+            parse("σ[id >"),
+            Error::Parse {
+                line: 1,
+                column: 7,
+                message: "expected an expression, found end of input".to_string(),
+            }
+        );
+        assert_eq!(
+            sql::plan("SELECT").unwrap_err(),
+            Error::Parse {
+                line: 1,
+                column: 7,
+                message: "expected an expression, found end of input".to_string(),
+            }
+        );
+        assert!(matches!(
+            parse("users ⟕ users"),
+            Error::Parse { column: 9, .. }
+        ));
+        assert!(matches!(
+            parse("σ[name = 'bob](users)"),
+            Error::Parse { column: 10, .. }
+        ));
+        assert!(matches!(
+            parse("users users"),
+            Error::Parse { column: 7, .. }
+        ));
+
+        assert_eq!(
+            "2024-02-29".parse::<Date>(),
+            Ok(Date::from_ymd(2024, 2, 29).unwrap())
+        );
+        assert!("2023-02-29".parse::<Date>().is_err());
+        assert_eq!(
+            "2024-01-31T10:20:30.5"
+                .parse::<Timestamp>()
+                .unwrap()
+                .to_string(),
+            "2024-01-31 10:20:30.500000"
+        );
+    }
+
+    #[test]
+    fn sql_select() {
+        let (users, orders) = create_users_and_orders();
+        let lines = create_lines();
+        let relations = [&users, &orders, &lines];
+        let run = |src: &str| {
+            sql::parse(src, &relations)
+                .and_then(|query| query.evaluate())
+                .map(|r| r.data.tuples())
+        };
+        let str = |s: &str| Value::Str(s.to_string());
+
+        let algebra = parser::parse("π[name](σ[id >= 2](users))", &relations).unwrap();
+        assert_eq!(
+            run("SELECT name FROM users WHERE id >= 2;"),
+            Ok(algebra.evaluate().unwrap().data.tuples())
+        );
+
+        assert_eq!(
+            run(
+                "select u.name, o.item from users u join orders as o on u.id = o.user_id \
+                 where o.item <> 'pad' order by item desc"
+            ),
+            Ok(vec![
+                vec![str("bob"), str("pen")],
+                vec![str("bob"), str("ink")]
+            ])
+        );
+        assert_eq!(
+            run("SELECT name, item FROM users LEFT OUTER JOIN orders \
+                 ON users.id = orders.user_id AND item = 'pad'"),
+            Ok(vec![
+                vec![str("bob"), Value::Null],
+                vec![str("alice"), str("pad")]
+            ])
+        );
+        // keys not in the select list are sorted on all the same
+        assert_eq!(
+            run("SELECT name FROM users ORDER BY id DESC"),
+            Ok(vec![vec![str("alice")], vec![str("bob")]])
+        );
+        assert_eq!(
+            run(
+                "SELECT u.name FROM users u JOIN orders o ON u.id = o.user_id \
+                 ORDER BY o.item, name LIMIT 2"
+            ),
+            Ok(vec![vec![str("bob")], vec![str("alice")]])
+        );
         assert_eq!(
-            schema.validate_row(&vec![Value::Int(1), Value::Str("foo".to_string())]),
-            true
-        )
-    }
-
-    #[test]
-    fn test_insert_row() {
-        let mut relation = create_test_relation();
+            run("SELECT id, price * qty AS total FROM lines ORDER BY qty DESC, total"),
+            Ok(vec![
+                vec![Value::Int(3), Value::Decimal("109.89".parse().unwrap())],
+                vec![Value::Int(1), Value::Decimal("25.00".parse().unwrap())],
+                vec![Value::Int(2), Value::Decimal("120.00".parse().unwrap())],
+            ])
+        );
+        let query = sql::parse("SELECT name FROM users ORDER BY id", &relations).unwrap();
+        let names = query
+            .evaluate()
+            .unwrap()
+            .schema
+            .attributes
+            .iter()
+            .map(|a| a.name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["name"]);
 
+        // a single relation answers to its name, or its alias if it has one
         assert_eq!(
-            relation.insert_row(vec![Value::Int(1), Value::Str("foo".to_string())]),
-            true
+            run("SELECT users.name FROM users WHERE users.id = 2"),
+            Ok(vec![vec![str("alice")]])
         );
-
-        println!("[TEST] data inserted: {:?}", &relation.data);
-
         assert_eq!(
-            relation.insert_row(vec![Value::Int(1), Value::Str("bar".to_string())]),
-            false
+            run("SELECT u.name FROM users u WHERE u.id > 1 AND abs(u.id) < 3"),
+            Ok(vec![vec![str("alice")]])
         );
-
-        println!("[TEST] duplicate row not inserted");
-
         assert_eq!(
-            relation.insert_rows(vec![
-                vec![Value::Int(2), Value::Str("foo".to_string())],
-                vec![Value::Int(3), Value::Str("bar".to_string())],
-            ]),
-            true
+            run("SELECT o.user_id, count(o.item) FROM orders AS o \
+                 GROUP BY o.user_id ORDER BY o.user_id DESC"),
+            Ok(vec![
+                vec![Value::Int(2), Value::Int(1)],
+                vec![Value::Int(1), Value::Int(2)]
+            ])
         );
-
-        println!("[TEST] multiple inserts {:?}", &relation.data);
-
         assert_eq!(
-            relation.insert_rows(vec![
-                vec![Value::Int(1), Value::Str("foo".to_string())],
-                vec![Value::Int(2), Value::Str("bar".to_string())],
-                vec![Value::Int(3), Value::Str("baz".to_string())],
-            ]),
-            false
+            run("SELECT users.name FROM users u"),
+            Err(Error::UnknownAttribute("users.name".to_string()))
         );
 
-        println!("[TEST] not inserting rows if duplicates found");
+        assert_eq!(
+            run(
+                "SELECT id, price * qty AS total FROM lines WHERE price * qty > 100 ORDER BY total"
+            ),
+            Ok(vec![
+                vec![Value::Int(3), Value::Decimal("109.89".parse().unwrap())],
+                vec![Value::Int(2), Value::Decimal("120.00".parse().unwrap())],
+            ])
+        );
 
+        let query = "SELECT user_id, count(*) AS n, min(item) FROM orders \
+                     GROUP BY user_id ORDER BY n DESC, user_id LIMIT 1";
+        assert!(matches!(
+            sql::plan(query),
+            Ok(sql::LogicalPlan::Limit(1, _))
+        ));
         assert_eq!(
-            relation.insert_rows(vec![
-                vec![Value::Int(4), Value::Str("apple".to_string())],
-                vec![Value::Int(5), Value::Str("orange".to_string())],
-                vec![Value::Int(6), Value::Str("orange".to_string())],
-            ]),
-            true
+            run(query),
+            Ok(vec![vec![Value::Int(1), Value::Int(2), str("ink")]])
+        );
+        assert_eq!(
+            run("SELECT count(*), sum(qty) FROM lines"),
+            Ok(vec![vec![Value::Int(3), Value::Int(22)]])
         );
 
-        println!("[TEST] multiple inserts {:?}", &relation.data);
+        assert_eq!(
+            run("SELECT * FROM people"),
+            Err(Error::UnknownRelation("people".to_string()))
+        );
+        assert_eq!(
+            run("SELECT name users"),
+            Err(Error::Parse {
+                line: 1,
+                column: 13,
+                message: "expected `FROM`, found `users`".to_string(),
+            })
+        );
     }
 
     #[test]
-    fn basic_projections() {
-        let mut relation = create_test_relation();
-
-        relation.insert_rows(vec![
-            vec![Value::Int(1), Value::Str("foo".to_string())],
-            vec![Value::Int(2), Value::Str("bar".to_string())],
-            vec![Value::Int(3), Value::Str("baz".to_string())],
-        ]);
-
-        let select_all = Operator::Unary(UnaryOpr::Projection(ProjAttrs::None, &relation));
-        let result = select_all.evaluate();
-
-        assert_eq!(result.is_some(), true);
+    fn database() {
+        let (users, orders) = create_users_and_orders();
+        let mut db = Database::new();
+        db.create(orders).unwrap();
+        db.create(users).unwrap();
 
+        let (users, _) = create_users_and_orders();
         assert_eq!(
-            result.as_ref().unwrap().data.tuples(),
-            vec![
-                vec![Value::Int(1), Value::Str("foo".to_string())],
-                vec![Value::Int(2), Value::Str("bar".to_string())],
-                vec![Value::Int(3), Value::Str("baz".to_string())],
-            ]
+            db.create(users),
+            Err(Error::DuplicateRelation("users".to_string()))
         );
+        assert_eq!(db.names().collect::<Vec<_>>(), vec!["orders", "users"]);
 
-        println!("[TEST] query result: {:?}", result.unwrap());
-
-        let select_value_attr = Operator::Unary(UnaryOpr::Projection(
-            ProjAttrs::Attr(
-                Attribute {
-                    name: "value".to_string(),
-                    atype: Type::Str,
-                },
+        db.get_mut("users")
+            .unwrap()
+            .insert_row(vec![Value::Int(3), Value::Str("carol".to_string())])
+            .unwrap();
+        let query = Operator::Binary(BinaryOpr::AntiJoin(
+            SelPredicate::Expr(
+                Expr::Compare(
+                    Box::new(Expr::Attr("users.id".to_string())),
+                    Comp::EQ,
+                    Box::new(Expr::Attr("orders.user_id".to_string())),
+                ),
                 None,
             ),
-            &relation,
+            db.input("users").unwrap(),
+            db.input("orders").unwrap(),
         ));
+        let expected = vec![vec![Value::Int(3), Value::Str("carol".to_string())]];
+        assert_eq!(query.evaluate().unwrap().data.tuples(), expected);
+        assert_eq!(
+            db.query("users ▷[users.id = orders.user_id] orders")
+                .unwrap()
+                .data
+                .tuples(),
+            expected
+        );
+        assert_eq!(
+            db.sql("SELECT count(*) FROM users").unwrap().data.tuples(),
+            vec![vec![Value::Int(3)]]
+        );
 
-        let result = select_value_attr.evaluate();
-        assert_eq!(result.is_some(), true);
-        let mut left = result.as_ref().unwrap().data.tuples();
-        let mut right = vec![
-            vec![Value::Str("foo".to_string())],
-            vec![Value::Str("bar".to_string())],
-            vec![Value::Str("baz".to_string())],
-        ];
-
-        left.sort();
-        right.sort();
-        assert_eq!(left, right);
-
-        println!("[TEST] selecting a single attribute {:?}", result);
+        assert_eq!(db.remove("orders").unwrap().name, "orders");
+        assert!(matches!(db.input("orders"), Err(Error::UnknownRelation(_))));
+        assert_eq!(
+            db.sql("SELECT * FROM orders").unwrap_err(),
+            Error::UnknownRelation("orders".to_string())
+        );
+        assert_eq!(db.names().collect::<Vec<_>>(), vec!["users"]);
     }
 
     #[test]
-    fn test_remove_duplicates() {
-        let mut relation = Relation {
-            name: "pk_less".to_string(),
-            pk: Some(0),
-            schema: Schema {
-                attributes: vec![
-                    Attribute {
-                        name: "id".to_string(),
-                        atype: Type::Int,
-                    },
-                    Attribute {
-                        name: "value".to_string(),
-                        atype: Type::Str,
-                    },
+    fn ddl() {
+        let mut db = Database::new();
+        db.execute(
+            "CREATE TABLE items (
+                id INTEGER PRIMARY KEY,
+                name VARCHAR(20) NOT NULL,
+                price DECIMAL(6, 2)
+            );",
+        )
+        .unwrap();
+        db.execute("create table tags (item int, tag text, primary key (item, tag))")
+            .unwrap();
+        db.execute("CREATE TABLE notes (body TEXT)").unwrap();
+
+        let items = db.get("items").unwrap();
+        assert!(matches!(items.data, Data::WithPK(_)));
+        assert_eq!(items.pk, Some(vec![0]));
+        assert!(!items.schema.attributes[0].nullable);
+        assert_eq!(items.schema.attributes[2].atype, Type::Decimal(6, 2));
+        assert!(items.schema.attributes[2].nullable);
+        assert_eq!(db.get("tags").unwrap().pk, Some(vec![0, 1]));
+        assert!(matches!(db.get("notes").unwrap().data, Data::NoPK(_)));
+
+        assert_eq!(
+            db.execute("CREATE TABLE notes (body TEXT)").unwrap_err(),
+            Error::DuplicateRelation("notes".to_string())
+        );
+        assert_eq!(
+            db.execute("CREATE TABLE bad (a INT, a TEXT)").unwrap_err(),
+            Error::DuplicateAttribute("a".to_string())
+        );
+        assert!(matches!(
+            db.execute("CREATE TABLE bad (a MONEY)"),
+            Err(Error::Parse { column: 21, .. })
+        ));
+
+        db.get_mut("items")
+            .unwrap()
+            .insert_rows(vec![
+                vec![
+                    Value::Int(1),
+                    Value::Str("pen".to_string()),
+                    Value::Decimal("2.50".parse().unwrap()),
                 ],
-            },
-            data: Data::WithPK(Box::new(BTreeMap::new())),
-        };
+                vec![Value::Int(2), Value::Str("ink".to_string()), Value::Null],
+            ])
+            .unwrap();
 
-        let insert_result = relation.insert_rows(vec![
-            vec![Value::Int(1), Value::Str("foo".to_string())],
-            vec![Value::Int(2), Value::Str("bar".to_string())],
-            vec![Value::Int(3), Value::Str("baz".to_string())],
-            vec![Value::Int(4), Value::Str("foo".to_string())],
-        ]);
+        db.execute("ALTER TABLE items ADD COLUMN stock INT")
+            .unwrap();
+        assert_eq!(
+            db.execute("ALTER TABLE items ADD flag BOOL NOT NULL")
+                .unwrap_err(),
+            Error::TypeMismatch("Null for flag Bool".to_string())
+        );
+        db.execute("ALTER TABLE items DROP COLUMN price").unwrap();
+        db.execute("ALTER TABLE items RENAME name TO label")
+            .unwrap();
+        assert_eq!(
+            db.execute("ALTER TABLE items RENAME label TO id")
+                .unwrap_err(),
+            Error::DuplicateAttribute("id".to_string())
+        );
 
-        assert!(insert_result);
+        let result = db.execute("SELECT * FROM items").unwrap().unwrap();
         assert_eq!(
-            relation.data.tuples(),
+            result
+                .schema
+                .attributes
+                .iter()
+                .map(|a| a.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["id", "label", "stock"]
+        );
+        assert_eq!(
+            result.data.tuples(),
             vec![
-                vec![Value::Int(1), Value::Str("foo".to_string())],
-                vec![Value::Int(2), Value::Str("bar".to_string())],
-                vec![Value::Int(3), Value::Str("baz".to_string())],
-                vec![Value::Int(4), Value::Str("foo".to_string())],
+                vec![Value::Int(1), Value::Str("pen".to_string()), Value::Null],
+                vec![Value::Int(2), Value::Str("ink".to_string()), Value::Null],
             ]
         );
 
-        let query = Operator::Unary(UnaryOpr::Projection(
-            ProjAttrs::Attr(
-                Attribute {
-                    name: "value".to_string(),
-                    atype: Type::Str,
-                },
-                None,
-            ),
-            &relation,
-        ));
-        let result = query.evaluate();
-        assert!(result.is_some());
-
-        let mut left = result.as_ref().unwrap().data.tuples();
-        let mut right = vec![
-            vec![Value::Str("foo".to_string())],
-            vec![Value::Str("bar".to_string())],
-            vec![Value::Str("baz".to_string())],
-        ];
-
-        left.sort();
-        right.sort();
-        assert_eq!(left, right);
+        // without its key, the relation keeps its tuples in a plain store
+        db.execute("ALTER TABLE items DROP id").unwrap();
+        let items = db.get("items").unwrap();
+        assert!(items.pk.is_none() && matches!(items.data, Data::NoPK(_)));
+        assert_eq!(items.data.tuples().len(), 2);
 
-        println!("[test] Project removed duplicate tuples");
+        db.execute("DROP TABLE items").unwrap();
+        assert_eq!(db.names().collect::<Vec<_>>(), vec!["notes", "tags"]);
+        assert_eq!(
+            db.execute("DROP TABLE items").unwrap_err(),
+            Error::UnknownRelation("items".to_string())
+        );
+    }
 
-        let derived = result.unwrap();
-        assert!(derived.pk.is_none());
+    #[test]
+    fn relation_builder() {
+        let mut users = Relation::builder("users")
+            .column("id", Type::Int)
+            .column("name", Type::Str)
+            .nullable_column("email", Type::Str)
+            .primary_key("id")
+            .build()
+            .unwrap();
+        assert_eq!(users.name(), "users");
+        assert_eq!(users.primary_key(), Some(&[0][..]));
+        assert!(matches!(users.data, Data::WithPK(_)));
+        assert_eq!(
+            users.schema().attributes()[2],
+            Attribute::new("email", Type::Str).nullable()
+        );
 
-        let derived_query = Operator::Unary(UnaryOpr::Projection(ProjAttrs::None, &derived));
-        let derived_query_result = derived_query.evaluate();
+        users
+            .insert_rows(vec![
+                vec![Value::Int(1), Value::Str("bob".to_string()), Value::Null],
+                vec![
+                    Value::Int(2),
+                    Value::Str("alice".to_string()),
+                    Value::Str("alice@example.com".to_string()),
+                ],
+            ])
+            .unwrap();
+        assert_eq!(users.tuples().len(), 2);
 
-        assert!(derived_query_result.is_some());
+        let mut db = Database::new();
+        db.create(users).unwrap();
+        assert_eq!(
+            db.sql("SELECT name FROM users WHERE email IS NULL")
+                .unwrap()
+                .tuples(),
+            vec![vec![Value::Str("bob".to_string())]]
+        );
 
-        let mut left = derived_query_result.as_ref().unwrap().data.tuples();
-        let mut right = vec![
-            vec![Value::Str("foo".to_string())],
-            vec![Value::Str("bar".to_string())],
-            vec![Value::Str("baz".to_string())],
-        ];
+        // a composite key, with the nullable attribute made non-nullable
+        let tags = Relation::builder("tags")
+            .column("item", Type::Int)
+            .nullable_column("tag", Type::Str)
+            .primary_key("item")
+            .primary_key("tag")
+            .build()
+            .unwrap();
+        assert_eq!(tags.primary_key(), Some(&[0, 1][..]));
+        assert!(!tags.schema().attributes()[1].is_nullable());
 
-        left.sort();
-        right.sort();
-        assert_eq!(left, right);
-        assert!(derived_query_result.unwrap().pk.is_none());
+        let notes = Relation::builder("notes")
+            .column("body", Type::Str)
+            .build()
+            .unwrap();
+        assert!(notes.primary_key().is_none());
+        assert!(matches!(notes.data, Data::NoPK(_)));
 
-        println!("[test] Project removed duplicate tuples");
+        assert_eq!(
+            Relation::builder("bad")
+                .column("id", Type::Int)
+                .primary_key("key")
+                .build()
+                .unwrap_err(),
+            Error::UnknownAttribute("key".to_string())
+        );
+        assert_eq!(
+            Relation::builder("bad")
+                .column("id", Type::Int)
+                .column("id", Type::Str)
+                .build()
+                .unwrap_err(),
+            Error::DuplicateAttribute("id".to_string())
+        );
     }
 
     #[test]
-    fn test_user_schema() {
-        // tbl users
-        // | id INT PK | name STR | phone INT
-        let mut relation = Relation {
-            name: "users".to_string(),
-            pk: Some(0),
-            schema: Schema {
-                attributes: vec![
-                    Attribute {
-                        name: "id".to_string(),
-                        atype: Type::Int,
-                    },
-                    Attribute {
-                        name: "name".to_string(),
-                        atype: Type::Str,
-                    },
-                    Attribute {
-                        name: "phone".to_string(),
-                        atype: Type::Int,
-                    },
-                ],
-            },
-            data: Data::WithPK(Box::new(BTreeMap::new())),
+    fn mixed_type_joins() {
+        let relation = |name: &str, atype: Type, values: Vec<Value>| {
+            let mut relation = Relation::builder(name)
+                .column("v", atype)
+                .primary_key("v")
+                .build()
+                .unwrap();
+            relation
+                .insert_rows(values.into_iter().map(|v| vec![v]).collect())
+                .unwrap();
+            relation
+        };
+        let ints = relation("a", Type::Int, vec![Value::Int(1), Value::Int(2)]);
+        let floats = relation("b", Type::Float, vec![Value::Float(Float(1.0))]);
+        let decimals = relation(
+            "c",
+            Type::Decimal(4, 1),
+            vec![Value::Decimal("2.0".parse().unwrap())],
+        );
+        let run = |src: &str| {
+            parser::parse(src, &[&ints, &floats, &decimals])
+                .and_then(|query| query.evaluate())
+                .map(|r| r.tuples())
         };
 
-        // 100 | bob | 9999999999
-        // 101 | alice | 6666666666
-        let insert_result = relation.insert_rows(vec![
-            vec![
-                Value::Int(100),
-                Value::Str("bob".to_string()),
-                Value::Int(9999999999),
-            ],
-            vec![
-                Value::Int(101),
-                Value::Str("alice".to_string()),
-                Value::Int(6666666666),
-            ],
-        ]);
-        assert!(insert_result);
-
-        // pi_{name, phone}
-        let query = Operator::Unary(UnaryOpr::Projection(
-            ProjAttrs::Attr(
-                Attribute {
-                    name: "name".to_string(),
-                    atype: Type::Str,
-                },
-                Some(Box::new(ProjAttrs::Attr(
-                    Attribute {
-                        name: "phone".to_string(),
-                        atype: Type::Int,
-                    },
-                    None,
-                ))),
-            ),
-            &relation,
-        ));
-
-        let result = query.evaluate();
-
-        // tbl derived
-        // bob | 9999999999
-        // alice | 6666666666
-        let mut left = result.as_ref().unwrap().data.tuples();
-        let mut right = vec![
-            vec![Value::Str("bob".to_string()), Value::Int(9999999999)],
-            vec![Value::Str("alice".to_string()), Value::Int(6666666666)],
-        ];
-
-        left.sort();
-        right.sort();
-        assert_eq!(left, right);
+        assert_eq!(
+            run("a join[a.v = b.v] b"),
+            Ok(vec![vec![Value::Int(1), Value::Float(Float(1.0))]])
+        );
+        assert_eq!(
+            run("a join[a.v = b.v] b"),
+            run("select[a.v = b.v](a times b)")
+        );
+        assert_eq!(
+            run("a join[a.v = c.v] c"),
+            Ok(vec![vec![
+                Value::Int(2),
+                Value::Decimal("2.0".parse().unwrap())
+            ]])
+        );
+        assert_eq!(
+            run("a semijoin[a.v = c.v] c"),
+            Ok(vec![vec![Value::Int(2)]])
+        );
+        assert_eq!(
+            run("a antijoin[a.v = b.v] b"),
+            Ok(vec![vec![Value::Int(2)]])
+        );
     }
 }
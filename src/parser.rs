@@ -0,0 +1,910 @@
+//! Relational algebra written as text, such as
+//! `σ[age > 30](π[name, age](users))`, parsed into an [`Operator`] tree.
+//!
+//! Unary operators come first, with their arguments in brackets and their
+//! input in parentheses. Binary operators go between their inputs and group
+//! to the left, so parentheses are needed to nest them otherwise. Every
+//! operator has a symbol and an ASCII keyword:
+//!
+//! | operator | symbol | keyword | arguments |
+//! |---|---|---|---|
+//! | selection | `σ` | `select` | `[predicate]` |
+//! | projection | `π` | `project` | `[name, expr AS alias, ...]` or `[*]` |
+//! | rename | `ρ` | `rename` | `[relation]`, `[old -> new, ...]` or both, split by `;` |
+//! | aggregation | `γ` | `group` | `[name, ...; count(*), sum(name), ...]` |
+//! | sort | `τ` | `sort` | `[name DESC, name ASC, ...]` |
+//! | limit | | `limit` | `[count]` |
+//! | offset | | `offset` | `[count]` |
+//! | union | `∪` | `union` | |
+//! | difference | `−` | `minus` | |
+//! | intersection | `∩` | `intersect` | |
+//! | product | `×` | `times` | |
+//! | natural or theta join | `⋈` | `join` | optional `[predicate]` |
+//! | left, right, full outer join | `⟕`, `⟖`, `⟗` | `leftjoin`, `rightjoin`, `fulljoin` | `[predicate]` |
+//! | semi and anti join | `⋉`, `▷` | `semijoin`, `antijoin` | `[predicate]` |
+//! | division | `÷` | `divide` | |
+//!
+//! Predicates combine comparisons with `AND`, `OR` and `NOT` (`∧`, `∨`,
+//! `¬`), and compare expressions with `=`, `<>`, `<`, `<=`, `>`, `>=` (also
+//! `≠`, `≤`, `≥`) or `IS [NOT] NULL`. Expressions have `+ - * / %`, `||`,
+//! the functions `abs`, `lower`, `upper`, `length` and `coalesce`, and the
+//! literals `12`, `1.50` (a DECIMAL), `1e3` (a FLOAT), `'text'`, `TRUE`,
+//! `FALSE`, `NULL`, `DATE '2024-01-31'` and `TIMESTAMP '2024-01-31 10:00:00'`.
+//! Keywords are case insensitive.
+//!
+//! The arguments of a unary operator may qualify names by the relation it
+//! applies to, so `σ[users.id = 1](users)` is `σ[id = 1](users)`. Selection,
+//! projection, sort, limit, offset and the semi and anti joins keep the name
+//! of their input, so `π[users.name](σ[users.id = 1](users))` works too.
+
+use crate::{
+    Aggregate, BinOp, BinaryOpr, Comp, Error, Expr, Float, Function, Input, Operand, Operator,
+    ProjAttrs, Relation, SelPredicate, SortKey, UnaryOpr, Value,
+};
+
+/// Parses `src` into an operator over `relations`, which it refers to by
+/// name. A bare relation gives the projection of all its attributes.
+pub fn parse<'a>(src: &str, relations: &[&'a Relation]) -> Result<Operator<'a>, Error> {
+    let mut parser = Parser::new(src)?;
+    let query = parser.query(relations)?;
+    parser.end()?;
+
+    Ok(match query {
+        Input::Operator(opr) => *opr,
+        input => Operator::Unary(UnaryOpr::Projection(ProjAttrs::None, input)),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Token {
+    /// A name or a keyword, qualified names such as `users.id` included
+    Ident(String),
+    Number(String),
+    Str(String),
+    Symbol(&'static str),
+    End,
+}
+
+/// Where a token is in the source
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Pos {
+    line: usize,
+    column: usize,
+    /// Byte offsets of the start and the end of the token
+    start: usize,
+    end: usize,
+}
+
+pub(crate) fn error(pos: Pos, message: impl Into<String>) -> Error {
+    Error::Parse {
+        line: pos.line,
+        column: pos.column,
+        message: message.into(),
+    }
+}
+
+/// Symbols that stand for a keyword
+const SYMBOL_KEYWORDS: &[(&str, &str)] = &[
+    ("σ", "select"),
+    ("π", "project"),
+    ("ρ", "rename"),
+    ("γ", "group"),
+    ("τ", "sort"),
+    ("∪", "union"),
+    ("−", "minus"),
+    ("∩", "intersect"),
+    ("×", "times"),
+    ("⋈", "join"),
+    ("⟕", "leftjoin"),
+    ("⟖", "rightjoin"),
+    ("⟗", "fulljoin"),
+    ("⋉", "semijoin"),
+    ("▷", "antijoin"),
+    ("÷", "divide"),
+    ("∧", "and"),
+    ("∨", "or"),
+    ("¬", "not"),
+];
+
+/// Symbols, longest first, with the spelling they are read as
+const SYMBOLS: &[(&str, &str)] = &[
+    ("<=", "<="),
+    (">=", ">="),
+    ("<>", "<>"),
+    ("!=", "<>"),
+    ("->", "->"),
+    ("||", "||"),
+    ("≤", "<="),
+    ("≥", ">="),
+    ("≠", "<>"),
+    ("→", "->"),
+    ("(", "("),
+    (")", ")"),
+    ("[", "["),
+    ("]", "]"),
+    (",", ","),
+    (";", ";"),
+    ("=", "="),
+    ("<", "<"),
+    (">", ">"),
+    ("+", "+"),
+    ("-", "-"),
+    ("*", "*"),
+    ("/", "/"),
+    ("%", "%"),
+];
+
+pub(crate) fn tokenize(src: &str) -> Result<Vec<(Token, Pos)>, Error> {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+
+    let mut tokens = vec![];
+    let (mut line, mut column, mut offset) = (1, 1, 0);
+    while let Some(c) = src[offset..].chars().next() {
+        let rest = &src[offset..];
+        let pos = Pos {
+            line,
+            column,
+            start: offset,
+            end: offset,
+        };
+
+        let (token, len) = if c.is_whitespace() {
+            (None, c.len_utf8())
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+            (Some(Token::Ident(rest[..len].to_string())), len)
+        } else if c.is_ascii_digit() {
+            let mut len = digits(rest);
+            if rest[len..].starts_with('.') {
+                len += 1 + digits(&rest[len + 1..]);
+            }
+            if rest[len..].starts_with(['e', 'E']) {
+                let sign = rest[len + 1..].starts_with(['+', '-']) as usize;
+                let exponent = digits(&rest[len + 1 + sign..]);
+                if exponent > 0 {
+                    len += 1 + sign + exponent;
+                }
+            }
+            (Some(Token::Number(rest[..len].to_string())), len)
+        } else if c == '\'' {
+            // a quote is escaped by doubling it
+            let mut text = String::new();
+            let mut chars = rest.char_indices().skip(1).peekable();
+            let len = loop {
+                match chars.next() {
+                    Some((i, '\'')) => match chars.peek() {
+                        Some((_, '\'')) => {
+                            text.push('\'');
+                            chars.next();
+                        }
+                        _ => break i + 1,
+                    },
+                    Some((_, c)) => text.push(c),
+                    None => return Err(error(pos, "unterminated string")),
+                }
+            };
+            (Some(Token::Str(text)), len)
+        } else if let Some((symbol, keyword)) =
+            SYMBOL_KEYWORDS.iter().find(|(s, _)| rest.starts_with(s))
+        {
+            (Some(Token::Ident(keyword.to_string())), symbol.len())
+        } else if let Some((symbol, read)) = SYMBOLS.iter().find(|(s, _)| rest.starts_with(s)) {
+            (Some(Token::Symbol(read)), symbol.len())
+        } else {
+            return Err(error(pos, format!("unexpected character {c:?}")));
+        };
+
+        for c in rest[..len].chars() {
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        offset += len;
+
+        if let Some(token) = token {
+            tokens.push((token, Pos { end: offset, ..pos }));
+        }
+    }
+
+    let end = Pos {
+        line,
+        column,
+        start: offset,
+        end: offset,
+    };
+    tokens.push((Token::End, end));
+
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(name) => format!("`{name}`"),
+        Token::Number(number) => number.clone(),
+        Token::Str(text) => format!("'{text}'"),
+        Token::Symbol(symbol) => format!("`{symbol}`"),
+        Token::End => "end of input".to_string(),
+    }
+}
+
+/// A recursive descent parser over the tokens of a source, with the parts
+/// shared by the algebra and SQL
+pub(crate) struct Parser<'s> {
+    src: &'s str,
+    tokens: Vec<(Token, Pos)>,
+    index: usize,
+}
+
+impl<'s> Parser<'s> {
+    pub(crate) fn new(src: &'s str) -> Result<Self, Error> {
+        Ok(Parser {
+            src,
+            tokens: tokenize(src)?,
+            index: 0,
+        })
+    }
+
+    pub(crate) fn peek(&self) -> &Token {
+        &self.tokens[self.index].0
+    }
+
+    /// The token after the next one
+    pub(crate) fn peek_second(&self) -> &Token {
+        &self.tokens[(self.index + 1).min(self.tokens.len() - 1)].0
+    }
+
+    pub(crate) fn pos(&self) -> Pos {
+        self.tokens[self.index].1
+    }
+
+    pub(crate) fn advance(&mut self) -> Token {
+        let token = self.tokens[self.index].0.clone();
+        if self.index + 1 < self.tokens.len() {
+            self.index += 1;
+        }
+
+        token
+    }
+
+    /// The source text from `start` up to the last token read
+    pub(crate) fn text_from(&self, start: Pos) -> &'s str {
+        let end = self.tokens[self.index.saturating_sub(1)].1.end;
+        self.src[start.start..end.max(start.start)].trim()
+    }
+
+    pub(crate) fn error(&self, message: impl Into<String>) -> Error {
+        error(self.pos(), message)
+    }
+
+    pub(crate) fn unexpected(&self, expected: &str) -> Error {
+        self.error(format!(
+            "expected {expected}, found {}",
+            describe(self.peek())
+        ))
+    }
+
+    pub(crate) fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Token::Ident(name) if name.eq_ignore_ascii_case(keyword))
+    }
+
+    /// Reads the keyword if it comes next
+    pub(crate) fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.advance();
+        }
+
+        found
+    }
+
+    pub(crate) fn expect_keyword(&mut self, keyword: &str) -> Result<(), Error> {
+        match self.keyword(keyword) {
+            true => Ok(()),
+            false => Err(self.unexpected(&format!("`{}`", keyword.to_uppercase()))),
+        }
+    }
+
+    /// Reads the symbol if it comes next
+    pub(crate) fn symbol(&mut self, symbol: &str) -> bool {
+        let found = *self.peek() == Token::Symbol(symbol_str(symbol));
+        if found {
+            self.advance();
+        }
+
+        found
+    }
+
+    pub(crate) fn expect(&mut self, symbol: &str) -> Result<(), Error> {
+        match self.symbol(symbol) {
+            true => Ok(()),
+            false => Err(self.unexpected(&format!("`{symbol}`"))),
+        }
+    }
+
+    pub(crate) fn ident(&mut self) -> Result<String, Error> {
+        match self.peek() {
+            Token::Ident(name) => {
+                let name = name.clone();
+                self.advance();
+                Ok(name)
+            }
+            _ => Err(self.unexpected("a name")),
+        }
+    }
+
+    pub(crate) fn count(&mut self) -> Result<usize, Error> {
+        match self.peek() {
+            Token::Number(n) => {
+                let count = n.parse().map_err(|_| self.unexpected("a count"))?;
+                self.advance();
+                Ok(count)
+            }
+            _ => Err(self.unexpected("a count")),
+        }
+    }
+
+    pub(crate) fn end(&self) -> Result<(), Error> {
+        match self.peek() {
+            Token::End => Ok(()),
+            _ => Err(self.unexpected("end of input")),
+        }
+    }
+
+    /// Items separated by commas, at least one
+    pub(crate) fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, Error>,
+    ) -> Result<Vec<T>, Error> {
+        let mut items = vec![item(self)?];
+        while self.symbol(",") {
+            items.push(item(self)?);
+        }
+
+        Ok(items)
+    }
+
+    /// Disjunctions of conjunctions, which bind tighter
+    pub(crate) fn predicate(&mut self) -> Result<SelPredicate, Error> {
+        let mut left = self.conjunction()?;
+        while self.keyword("or") {
+            left = SelPredicate::Or(Box::new(left), Box::new(self.conjunction()?));
+        }
+
+        Ok(left)
+    }
+
+    fn conjunction(&mut self) -> Result<SelPredicate, Error> {
+        let mut left = self.negation()?;
+        while self.keyword("and") {
+            left = SelPredicate::And(Box::new(left), Box::new(self.negation()?));
+        }
+
+        Ok(left)
+    }
+
+    fn negation(&mut self) -> Result<SelPredicate, Error> {
+        if self.keyword("not") {
+            return Ok(SelPredicate::Not(Box::new(self.negation()?)));
+        }
+
+        // parentheses group a predicate, unless they only start an
+        // expression, as in `(a + b) > c`
+        if *self.peek() == Token::Symbol("(") {
+            let start = self.index;
+            self.advance();
+            if let Ok(predicate) = self.predicate()
+                && self.symbol(")")
+                && !self.continues_expr()
+            {
+                return Ok(predicate);
+            }
+            self.index = start;
+        }
+
+        Ok(SelPredicate::Expr(self.comparison()?, None))
+    }
+
+    fn continues_expr(&self) -> bool {
+        matches!(
+            self.peek(),
+            Token::Symbol(
+                "=" | "<>" | "<" | "<=" | ">" | ">=" | "+" | "-" | "*" | "/" | "%" | "||"
+            )
+        ) || self.is_keyword("is")
+    }
+
+    /// An expression, compared with another one if a comparison follows
+    pub(crate) fn comparison(&mut self) -> Result<Expr, Error> {
+        let left = self.expr()?;
+
+        if self.keyword("is") {
+            let comp = match self.keyword("not") {
+                true => Comp::IsNotNull,
+                false => Comp::IsNull,
+            };
+            self.expect_keyword("null")?;
+            return Ok(Expr::Compare(
+                Box::new(left),
+                comp,
+                Box::new(Expr::Value(Value::Null)),
+            ));
+        }
+
+        let comp = match self.peek() {
+            Token::Symbol("=") => Comp::EQ,
+            Token::Symbol("<>") => Comp::NE,
+            Token::Symbol("<") => Comp::LT,
+            Token::Symbol("<=") => Comp::LE,
+            Token::Symbol(">") => Comp::GT,
+            Token::Symbol(">=") => Comp::GE,
+            _ => return Ok(left),
+        };
+        self.advance();
+
+        Ok(Expr::Compare(Box::new(left), comp, Box::new(self.expr()?)))
+    }
+
+    /// Sums and concatenations of terms
+    pub(crate) fn expr(&mut self) -> Result<Expr, Error> {
+        let mut left = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Token::Symbol("+") => BinOp::Add,
+                Token::Symbol("-") => BinOp::Sub,
+                Token::Symbol("||") => BinOp::Concat,
+                _ => return Ok(left),
+            };
+            self.advance();
+            left = Expr::Binary(Box::new(left), op, Box::new(self.term()?));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, Error> {
+        let mut left = self.factor()?;
+        loop {
+            let op = match self.peek() {
+                Token::Symbol("*") => BinOp::Mul,
+                Token::Symbol("/") => BinOp::Div,
+                Token::Symbol("%") => BinOp::Mod,
+                _ => return Ok(left),
+            };
+            self.advance();
+            left = Expr::Binary(Box::new(left), op, Box::new(self.factor()?));
+        }
+    }
+
+    fn factor(&mut self) -> Result<Expr, Error> {
+        if !self.symbol("-") {
+            return self.primary();
+        }
+
+        Ok(match self.factor()? {
+            Expr::Value(Value::Int(i)) if i != i64::MIN => Expr::Value(Value::Int(-i)),
+            Expr::Value(Value::Float(f)) => Expr::Value(Value::Float(Float(-f.0))),
+            expr => Expr::Neg(Box::new(expr)),
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let pos = self.pos();
+        match self.advance() {
+            Token::Number(n) => number(&n)
+                .map(Expr::Value)
+                .map_err(|e| error(pos, e.to_string())),
+            Token::Str(text) => Ok(Expr::Value(Value::Str(text))),
+            Token::Symbol("(") => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Ident(name) => self.named(name, pos),
+            token => Err(error(
+                pos,
+                format!("expected an expression, found {}", describe(&token)),
+            )),
+        }
+    }
+
+    /// A keyword literal, a function call or an attribute
+    fn named(&mut self, name: String, pos: Pos) -> Result<Expr, Error> {
+        let typed = |text: &str, value: Result<Value, Error>| {
+            value.map_err(|_| error(pos, format!("'{text}' is not a {}", name.to_uppercase())))
+        };
+
+        let value = match (name.to_ascii_lowercase().as_str(), self.peek().clone()) {
+            ("null", _) => Value::Null,
+            ("true", _) => Value::Bool(true),
+            ("false", _) => Value::Bool(false),
+            ("date", Token::Str(text)) => {
+                self.advance();
+                typed(&text, text.parse().map(Value::Date))?
+            }
+            ("timestamp", Token::Str(text)) => {
+                self.advance();
+                typed(&text, text.parse().map(Value::Timestamp))?
+            }
+            (function, Token::Symbol("(")) => {
+                let function = match function {
+                    "abs" => Function::Abs,
+                    "lower" => Function::Lower,
+                    "upper" => Function::Upper,
+                    "length" => Function::Length,
+                    "coalesce" => Function::Coalesce,
+                    _ => return Err(error(pos, format!("unknown function {name}"))),
+                };
+                self.advance();
+                let args = self.list(Self::expr)?;
+                self.expect(")")?;
+                return Ok(Expr::Call(function, args));
+            }
+            _ => return Ok(Expr::Attr(name)),
+        };
+
+        Ok(Expr::Value(value))
+    }
+
+    /// An aggregate such as `count(*)` or `sum(qty)`
+    pub(crate) fn aggregate(&mut self) -> Result<Aggregate, Error> {
+        let pos = self.pos();
+        let function = self.ident()?.to_ascii_lowercase();
+        self.expect("(")?;
+        if function == "count" && self.symbol("*") {
+            self.expect(")")?;
+            return Ok(Aggregate::Count(None));
+        }
+
+        let attr = self.ident()?;
+        self.expect(")")?;
+        Ok(match function.as_str() {
+            "count" => Aggregate::Count(Some(attr)),
+            "sum" => Aggregate::Sum(attr),
+            "min" => Aggregate::Min(attr),
+            "max" => Aggregate::Max(attr),
+            "avg" => Aggregate::Avg(attr),
+            _ => return Err(error(pos, format!("unknown aggregate {function}"))),
+        })
+    }
+
+    /// A sort key, ascending unless followed by `DESC`
+    pub(crate) fn sort_key(&mut self) -> Result<SortKey, Error> {
+        let name = self.ident()?;
+        if self.keyword("desc") {
+            return Ok(SortKey::Desc(name));
+        }
+        self.keyword("asc");
+
+        Ok(SortKey::Asc(name))
+    }
+
+    /// Operands joined by binary operators, grouping to the left
+    fn query<'a>(&mut self, relations: &[&'a Relation]) -> Result<Input<'a>, Error> {
+        let mut left = self.operand(relations)?;
+
+        loop {
+            let keyword = match self.peek() {
+                Token::Ident(name) => name.to_ascii_lowercase(),
+                _ => return Ok(left),
+            };
+            let joins = [
+                "join",
+                "leftjoin",
+                "rightjoin",
+                "fulljoin",
+                "semijoin",
+                "antijoin",
+            ];
+            let others = ["union", "minus", "intersect", "times", "divide"];
+            if !joins.contains(&keyword.as_str()) && !others.contains(&keyword.as_str()) {
+                return Ok(left);
+            }
+            self.advance();
+
+            let predicate = match keyword.as_str() {
+                "join" if *self.peek() != Token::Symbol("[") => None,
+                _ if joins.contains(&keyword.as_str()) => {
+                    self.expect("[")?;
+                    let predicate = self.predicate()?;
+                    self.expect("]")?;
+                    Some(predicate)
+                }
+                _ => None,
+            };
+            let right = self.operand(relations)?;
+
+            let opr = match (keyword.as_str(), predicate) {
+                ("union", _) => BinaryOpr::Union(left, right),
+                ("minus", _) => BinaryOpr::Difference(left, right),
+                ("intersect", _) => BinaryOpr::Intersection(left, right),
+                ("times", _) => BinaryOpr::Product(left, right),
+                ("divide", _) => BinaryOpr::Division(left, right),
+                ("join", None) => BinaryOpr::NaturalJoin(left, right),
+                ("join", Some(p)) => BinaryOpr::ThetaJoin(p, left, right),
+                ("leftjoin", Some(p)) => BinaryOpr::LeftOuterJoin(p, left, right),
+                ("rightjoin", Some(p)) => BinaryOpr::RightOuterJoin(p, left, right),
+                ("fulljoin", Some(p)) => BinaryOpr::FullOuterJoin(p, left, right),
+                ("semijoin", Some(p)) => BinaryOpr::SemiJoin(p, left, right),
+                (_, p) => BinaryOpr::AntiJoin(p.unwrap_or(SelPredicate::None), left, right),
+            };
+            left = Operator::Binary(opr).into();
+        }
+    }
+
+    /// A relation, a unary operator or a parenthesized query
+    fn operand<'a>(&mut self, relations: &[&'a Relation]) -> Result<Input<'a>, Error> {
+        if self.symbol("(") {
+            let query = self.query(relations)?;
+            self.expect(")")?;
+            return Ok(query);
+        }
+
+        let pos = self.pos();
+        let name = self.ident()?;
+        let keyword = name.to_ascii_lowercase();
+        let unary = [
+            "select", "project", "rename", "group", "sort", "limit", "offset",
+        ];
+
+        // a relation may share its name with a keyword, which is only one
+        // when brackets follow
+        if !unary.contains(&keyword.as_str()) || *self.peek() != Token::Symbol("[") {
+            return relations
+                .iter()
+                .find(|r| r.name == name)
+                .map(|r| Input::Relation(r))
+                .ok_or_else(|| error(pos, format!("unknown relation {name}")));
+        }
+
+        self.expect("[")?;
+        let mut opr = match keyword.as_str() {
+            "select" => Args::Selection(self.predicate()?),
+            "project" => Args::Projection(self.projection()?),
+            "rename" => {
+                let (name, attrs) = self.rename()?;
+                Args::Rename(name, attrs)
+            }
+            "group" => {
+                let group_by = match self.peek() {
+                    Token::Symbol(";" | "]") => vec![],
+                    _ => self.list(Self::ident)?,
+                };
+                let aggregates = match self.symbol(";") {
+                    true => self.list(Self::aggregate)?,
+                    false => vec![],
+                };
+                Args::Aggregation(group_by, aggregates)
+            }
+            "sort" => Args::Sort(self.list(Self::sort_key)?),
+            "limit" => Args::Limit(self.count()?),
+            _ => Args::Offset(self.count()?),
+        };
+        self.expect("]")?;
+
+        self.expect("(")?;
+        let input = self.query(relations)?;
+        self.expect(")")?;
+
+        // the attributes of a relation are not qualified by its name
+        let qualifier = input.name();
+        match &mut opr {
+            Args::Selection(p) => unqualify_predicate(p, qualifier),
+            Args::Projection(p) => unqualify_projection(p, qualifier),
+            Args::Rename(_, attrs) => {
+                for (from, _) in attrs {
+                    unqualify(from, qualifier);
+                }
+            }
+            Args::Aggregation(group_by, aggs) => {
+                for name in group_by {
+                    unqualify(name, qualifier);
+                }
+                for agg in aggs {
+                    unqualify_aggregate(agg, qualifier);
+                }
+            }
+            Args::Sort(keys) => {
+                for SortKey::Asc(name) | SortKey::Desc(name) in keys {
+                    unqualify(name, qualifier);
+                }
+            }
+            Args::Limit(_) | Args::Offset(_) => {}
+        }
+
+        let opr = match opr {
+            Args::Selection(p) => UnaryOpr::Selection(p, input),
+            Args::Projection(p) => UnaryOpr::Projection(p, input),
+            Args::Rename(name, attrs) => UnaryOpr::Rename(name, attrs, input),
+            Args::Aggregation(group_by, aggs) => UnaryOpr::Aggregation(group_by, aggs, input),
+            Args::Sort(keys) => UnaryOpr::Sort(keys, input),
+            Args::Limit(count) => UnaryOpr::Limit(count, input),
+            Args::Offset(count) => UnaryOpr::Offset(count, input),
+        };
+
+        Ok(Operator::Unary(opr).into())
+    }
+
+    /// Columns of a projection, `*` for all of them
+    pub(crate) fn projection(&mut self) -> Result<ProjAttrs, Error> {
+        if self.symbol("*") {
+            return Ok(ProjAttrs::None);
+        }
+
+        Ok(columns(self.list(Self::column)?))
+    }
+
+    /// An expression and its alias, which defaults to the attribute name or
+    /// else the text of the expression
+    pub(crate) fn column(&mut self) -> Result<(Expr, String), Error> {
+        let start = self.pos();
+        let expr = self.expr()?;
+        let alias = match (self.keyword("as"), &expr) {
+            (true, _) => self.ident()?,
+            (false, Expr::Attr(name)) => name.clone(),
+            (false, _) => self.text_from(start).to_string(),
+        };
+
+        Ok((expr, alias))
+    }
+
+    /// A new name for the relation, pairs of old and new attribute names,
+    /// or both
+    fn rename(&mut self) -> Result<Renaming, Error> {
+        let pair = |parser: &mut Self| {
+            let from = parser.ident()?;
+            parser.expect("->")?;
+            Ok((from, parser.ident()?))
+        };
+
+        if *self.peek_second() == Token::Symbol("->") {
+            return Ok((None, self.list(pair)?));
+        }
+
+        let name = self.ident()?;
+        let attrs = match self.symbol(";") {
+            true => self.list(pair)?,
+            false => vec![],
+        };
+
+        Ok((Some(name), attrs))
+    }
+}
+
+/// The arguments of a rename
+type Renaming = (Option<String>, Vec<(String, String)>);
+
+/// Arguments of a unary operator, read before its input
+enum Args {
+    Selection(SelPredicate),
+    Projection(ProjAttrs),
+    Rename(Option<String>, Vec<(String, String)>),
+    Aggregation(Vec<String>, Vec<Aggregate>),
+    Sort(Vec<SortKey>),
+    Limit(usize),
+    Offset(usize),
+}
+
+/// Links computed columns into a projection
+pub(crate) fn columns(columns: Vec<(Expr, String)>) -> ProjAttrs {
+    columns
+        .into_iter()
+        .rev()
+        .fold(ProjAttrs::None, |next, (expr, alias)| {
+            let next = match next {
+                ProjAttrs::None => None,
+                next => Some(Box::new(next)),
+            };
+            ProjAttrs::Expr(expr, alias, next)
+        })
+}
+
+/// Drops `qualifier` from a name it qualifies, so `users.name` is `name`
+pub(crate) fn unqualify(name: &mut String, qualifier: Option<&str>) {
+    if let Some(rest) = qualifier
+        .and_then(|qualifier| name.strip_prefix(qualifier))
+        .and_then(|rest| rest.strip_prefix('.'))
+    {
+        *name = rest.to_string();
+    }
+}
+
+pub(crate) fn unqualify_expr(expr: &mut Expr, qualifier: Option<&str>) {
+    match expr {
+        Expr::Attr(name) => unqualify(name, qualifier),
+        Expr::Value(_) => {}
+        Expr::Neg(expr) => unqualify_expr(expr, qualifier),
+        Expr::Binary(left, _, right) | Expr::Compare(left, _, right) => {
+            unqualify_expr(left, qualifier);
+            unqualify_expr(right, qualifier);
+        }
+        Expr::Call(_, args) => {
+            for arg in args {
+                unqualify_expr(arg, qualifier);
+            }
+        }
+    }
+}
+
+pub(crate) fn unqualify_aggregate(agg: &mut Aggregate, qualifier: Option<&str>) {
+    match agg {
+        Aggregate::Count(None) => {}
+        Aggregate::Count(Some(name))
+        | Aggregate::Sum(name)
+        | Aggregate::Min(name)
+        | Aggregate::Max(name)
+        | Aggregate::Avg(name) => unqualify(name, qualifier),
+    }
+}
+
+pub(crate) fn unqualify_predicate(predicate: &mut SelPredicate, qualifier: Option<&str>) {
+    let next = match predicate {
+        SelPredicate::Condition((attr, _, operand), next) => {
+            unqualify(&mut attr.name, qualifier);
+            if let Operand::Attr(attr) = operand {
+                unqualify(&mut attr.name, qualifier);
+            }
+            next
+        }
+        SelPredicate::Expr(expr, next) => {
+            unqualify_expr(expr, qualifier);
+            next
+        }
+        SelPredicate::And(left, right) | SelPredicate::Or(left, right) => {
+            unqualify_predicate(left, qualifier);
+            unqualify_predicate(right, qualifier);
+            return;
+        }
+        SelPredicate::Not(predicate) => return unqualify_predicate(predicate, qualifier),
+        SelPredicate::None => return,
+    };
+
+    if let Some((_, next)) = next {
+        unqualify_predicate(next, qualifier);
+    }
+}
+
+fn unqualify_projection(columns: &mut ProjAttrs, qualifier: Option<&str>) {
+    let next = match columns {
+        ProjAttrs::Attr(attr, next) => {
+            unqualify(&mut attr.name, qualifier);
+            next
+        }
+        ProjAttrs::Expr(expr, _, next) => {
+            unqualify_expr(expr, qualifier);
+            next
+        }
+        ProjAttrs::None => return,
+    };
+
+    if let Some(next) = next {
+        unqualify_projection(next, qualifier);
+    }
+}
+
+/// The spelling a symbol is read as
+fn symbol_str(symbol: &str) -> &'static str {
+    SYMBOLS
+        .iter()
+        .find(|(s, _)| *s == symbol)
+        .map(|(_, read)| *read)
+        .unwrap_or("")
+}
+
+/// An INT, a DECIMAL when there is a point or the number is too large for
+/// an INT, or a FLOAT when there is an exponent
+fn number(n: &str) -> Result<Value, Error> {
+    if n.contains(['e', 'E']) {
+        return n
+            .parse()
+            .map(|f| Value::Float(Float(f)))
+            .map_err(|_| Error::TypeMismatch(format!("{n} is not a number")));
+    }
+
+    match n.parse() {
+        Ok(i) => Ok(Value::Int(i)),
+        Err(_) => n.parse().map(Value::Decimal),
+    }
+}
@@ -0,0 +1,524 @@
+//! A subset of SQL: `SELECT`, planned as relational algebra, and the
+//! statements that define relations.
+//!
+//! ```sql
+//! SELECT [DISTINCT] * | column [AS alias], ...
+//! FROM relation [[AS] alias]
+//!     [[INNER] JOIN | LEFT [OUTER] JOIN | RIGHT [OUTER] JOIN | FULL [OUTER] JOIN relation ON predicate]
+//!     [CROSS JOIN | NATURAL JOIN | , relation] ...
+//! [WHERE predicate]
+//! [GROUP BY name, ...]
+//! [ORDER BY name [ASC | DESC], ...]
+//! [LIMIT count] [OFFSET count]
+//! ```
+//!
+//! ```sql
+//! CREATE TABLE relation (name type [NOT NULL | NULL | PRIMARY KEY], ..., [PRIMARY KEY (name, ...)])
+//! DROP TABLE relation
+//! ALTER TABLE relation ADD [COLUMN] name type [NOT NULL | NULL]
+//! ALTER TABLE relation DROP [COLUMN] name
+//! ALTER TABLE relation RENAME [COLUMN] name TO name
+//! ```
+//!
+//! A column is an expression or an aggregate, `count(*)`, `count(name)`,
+//! `sum(name)`, `min(name)`, `max(name)` or `avg(name)`. Expressions and
+//! predicates are written as in the [algebra](crate::parser).
+//!
+//! A query is parsed into a [`LogicalPlan`] over relation names first, and
+//! [`LogicalPlan::bind`] turns it into an [`Operator`] over relations, so
+//! SQL means exactly what the algebra it is planned as means. In particular
+//! relations are sets, so there are never duplicate tuples and `DISTINCT`
+//! changes nothing.
+
+use crate::parser::{
+    Parser, Token, columns, error, unqualify, unqualify_aggregate, unqualify_expr,
+    unqualify_predicate,
+};
+use crate::{
+    Aggregate, Attribute, BinaryOpr, Decimal, Error, Expr, Input, Operator, ProjAttrs, Relation,
+    SelPredicate, SortKey, Type, UnaryOpr,
+};
+
+/// Parses a `SELECT` into its plan
+pub fn plan(src: &str) -> Result<LogicalPlan, Error> {
+    match statement(src)? {
+        Statement::Select(plan) => Ok(plan),
+        _ => Err(error(Parser::new(src)?.pos(), "expected `SELECT`")),
+    }
+}
+
+/// Parses a single statement
+pub fn statement(src: &str) -> Result<Statement, Error> {
+    let mut parser = Parser::new(src)?;
+    let statement = if parser.keyword("create") {
+        create_table(&mut parser)?
+    } else if parser.keyword("drop") {
+        parser.expect_keyword("table")?;
+        Statement::DropTable(parser.ident()?)
+    } else if parser.keyword("alter") {
+        alter_table(&mut parser)?
+    } else {
+        Statement::Select(select(&mut parser)?)
+    };
+    parser.symbol(";");
+    parser.end()?;
+
+    Ok(statement)
+}
+
+#[derive(Debug)]
+pub enum Statement {
+    Select(LogicalPlan),
+    /// A new relation with no tuples
+    CreateTable(Relation),
+    DropTable(String),
+    /// A change to the attributes of the named relation, which rewrites the
+    /// tuples it holds
+    AlterTable(String, Alteration),
+}
+
+#[derive(Debug)]
+pub enum Alteration {
+    /// An attribute added last, NULL in the tuples already stored
+    AddColumn(Attribute),
+    /// An attribute removed, along with the primary key if it is part of it
+    DropColumn(String),
+    RenameColumn(String, String),
+}
+
+/// Parses a `SELECT` into an operator over `relations`, which it refers to
+/// by name
+pub fn parse<'a>(src: &str, relations: &[&'a Relation]) -> Result<Operator<'a>, Error> {
+    plan(src)?.bind(relations)
+}
+
+/// A query as relational algebra over relations known by name. A `SELECT`
+/// is planned bottom up as
+///
+/// 1. a `Scan` of each relation in `FROM`, in a `Rename` when it has an
+///    alias, so that joined attributes are qualified by the alias. With a
+///    single relation, names qualified by it or its alias lose the qualifier.
+/// 2. a `Join` of each relation with the ones before it
+/// 3. a `Selection` for `WHERE`
+/// 4. an `Aggregation` for `GROUP BY`, or for aggregates in the select list,
+///    whose results are named like `sum(qty)` or `count`
+/// 5. a `Projection` of the select list, along with the `ORDER BY` keys
+///    that are not in it
+/// 6. a `Sort` for `ORDER BY`, then a `Projection` of the select list alone
+///    when keys were added to it
+/// 7. an `Offset` and then a `Limit`
+#[derive(Debug)]
+pub enum LogicalPlan {
+    Scan(String),
+    Rename(String, Box<LogicalPlan>),
+    Join(Join, Box<LogicalPlan>, Box<LogicalPlan>),
+    Selection(SelPredicate, Box<LogicalPlan>),
+    Aggregation(Vec<String>, Vec<Aggregate>, Box<LogicalPlan>),
+    Projection(ProjAttrs, Box<LogicalPlan>),
+    Sort(Vec<SortKey>, Box<LogicalPlan>),
+    Offset(usize, Box<LogicalPlan>),
+    Limit(usize, Box<LogicalPlan>),
+}
+
+/// How a `JOIN` combines its two sides
+#[derive(Debug)]
+pub enum Join {
+    /// `CROSS JOIN`, or relations separated by commas
+    Cross,
+    Natural,
+    Inner(SelPredicate),
+    Left(SelPredicate),
+    Right(SelPredicate),
+    Full(SelPredicate),
+}
+
+impl LogicalPlan {
+    /// The operator computing the plan, with each relation it scans looked
+    /// up by name in `relations`
+    pub fn bind<'a>(self, relations: &[&'a Relation]) -> Result<Operator<'a>, Error> {
+        Ok(match self.input(relations)? {
+            Input::Operator(opr) => *opr,
+            input => Operator::Unary(UnaryOpr::Projection(ProjAttrs::None, input)),
+        })
+    }
+
+    fn input<'a>(self, relations: &[&'a Relation]) -> Result<Input<'a>, Error> {
+        let opr = match self {
+            LogicalPlan::Scan(name) => {
+                return relations
+                    .iter()
+                    .find(|r| r.name == name)
+                    .map(|r| Input::Relation(r))
+                    .ok_or(Error::UnknownRelation(name));
+            }
+            LogicalPlan::Join(join, left, right) => {
+                let (left, right) = (left.input(relations)?, right.input(relations)?);
+                Operator::Binary(match join {
+                    Join::Cross => BinaryOpr::Product(left, right),
+                    Join::Natural => BinaryOpr::NaturalJoin(left, right),
+                    Join::Inner(p) => BinaryOpr::ThetaJoin(p, left, right),
+                    Join::Left(p) => BinaryOpr::LeftOuterJoin(p, left, right),
+                    Join::Right(p) => BinaryOpr::RightOuterJoin(p, left, right),
+                    Join::Full(p) => BinaryOpr::FullOuterJoin(p, left, right),
+                })
+            }
+            LogicalPlan::Rename(name, input) => Operator::Unary(UnaryOpr::Rename(
+                Some(name),
+                vec![],
+                input.input(relations)?,
+            )),
+            LogicalPlan::Selection(p, input) => {
+                Operator::Unary(UnaryOpr::Selection(p, input.input(relations)?))
+            }
+            LogicalPlan::Aggregation(group_by, aggs, input) => Operator::Unary(
+                UnaryOpr::Aggregation(group_by, aggs, input.input(relations)?),
+            ),
+            LogicalPlan::Projection(attrs, input) => {
+                Operator::Unary(UnaryOpr::Projection(attrs, input.input(relations)?))
+            }
+            LogicalPlan::Sort(keys, input) => {
+                Operator::Unary(UnaryOpr::Sort(keys, input.input(relations)?))
+            }
+            LogicalPlan::Offset(count, input) => {
+                Operator::Unary(UnaryOpr::Offset(count, input.input(relations)?))
+            }
+            LogicalPlan::Limit(count, input) => {
+                Operator::Unary(UnaryOpr::Limit(count, input.input(relations)?))
+            }
+        };
+
+        Ok(opr.into())
+    }
+}
+
+/// Words that end a relation in `FROM` rather than alias it
+const CLAUSES: &[&str] = &[
+    "where", "group", "order", "limit", "offset", "join", "inner", "left", "right", "full",
+    "cross", "natural", "on",
+];
+
+/// One column of the select list
+enum Item {
+    Column(Expr, String),
+    Aggregate(Aggregate, Option<String>),
+}
+
+fn select(parser: &mut Parser) -> Result<LogicalPlan, Error> {
+    parser.expect_keyword("select")?;
+    if !parser.keyword("distinct") {
+        parser.keyword("all");
+    }
+
+    let mut items = match parser.symbol("*") {
+        true => None,
+        false => Some(parser.list(item)?),
+    };
+
+    parser.expect_keyword("from")?;
+    let mut plan = relation(parser)?;
+    loop {
+        let join = if parser.symbol(",") {
+            Join::Cross
+        } else if parser.keyword("cross") {
+            parser.expect_keyword("join")?;
+            Join::Cross
+        } else if parser.keyword("natural") {
+            parser.expect_keyword("join")?;
+            Join::Natural
+        } else {
+            let kind = ["inner", "left", "right", "full"]
+                .into_iter()
+                .find(|kind| parser.keyword(kind));
+            if kind.is_none() && !parser.is_keyword("join") {
+                break;
+            }
+            if kind.is_some_and(|kind| kind != "inner") {
+                parser.keyword("outer");
+            }
+            parser.expect_keyword("join")?;
+
+            let right = relation(parser)?;
+            parser.expect_keyword("on")?;
+            let predicate = parser.predicate()?;
+            let join = match kind {
+                Some("left") => Join::Left(predicate),
+                Some("right") => Join::Right(predicate),
+                Some("full") => Join::Full(predicate),
+                _ => Join::Inner(predicate),
+            };
+            plan = LogicalPlan::Join(join, plan.into(), right.into());
+            continue;
+        };
+        plan = LogicalPlan::Join(join, plan.into(), relation(parser)?.into());
+    }
+
+    // the attributes of a single relation are not qualified by its name
+    let qualifier = match &plan {
+        LogicalPlan::Scan(name) | LogicalPlan::Rename(name, _) => Some(name.clone()),
+        _ => None,
+    };
+    let qualifier = qualifier.as_deref();
+    for item in items.iter_mut().flatten() {
+        match item {
+            Item::Column(expr, _) => unqualify_expr(expr, qualifier),
+            Item::Aggregate(agg, _) => unqualify_aggregate(agg, qualifier),
+        }
+    }
+
+    if parser.keyword("where") {
+        let mut predicate = parser.predicate()?;
+        unqualify_predicate(&mut predicate, qualifier);
+        plan = LogicalPlan::Selection(predicate, plan.into());
+    }
+
+    let mut group_by = match parser.keyword("group") {
+        true => {
+            parser.expect_keyword("by")?;
+            parser.list(Parser::ident)?
+        }
+        false => vec![],
+    };
+    for name in &mut group_by {
+        unqualify(name, qualifier);
+    }
+
+    let mut projected = vec![];
+    if let Some(items) = items {
+        let mut aggregates = vec![];
+        for item in items {
+            projected.push(match item {
+                Item::Column(expr, alias) => (expr, alias),
+                Item::Aggregate(agg, alias) => {
+                    let name = agg.name();
+                    aggregates.push(agg);
+                    (Expr::Attr(name.clone()), alias.unwrap_or(name))
+                }
+            });
+        }
+
+        if !group_by.is_empty() || !aggregates.is_empty() {
+            plan = LogicalPlan::Aggregation(group_by, aggregates, plan.into());
+        }
+    } else if !group_by.is_empty() {
+        plan = LogicalPlan::Aggregation(group_by, vec![], plan.into());
+    }
+
+    let mut keys = vec![];
+    if parser.keyword("order") {
+        parser.expect_keyword("by")?;
+        keys = parser.list(Parser::sort_key)?;
+        for SortKey::Asc(name) | SortKey::Desc(name) in &mut keys {
+            unqualify(name, qualifier);
+        }
+    }
+
+    // keys missing from the select list are projected along with it for
+    // the sort, and projected away after it
+    let selected = projected
+        .iter()
+        .map(|(_, alias)| (Expr::Attr(alias.clone()), alias.clone()))
+        .collect::<Vec<_>>();
+    for SortKey::Asc(name) | SortKey::Desc(name) in keys.iter().filter(|_| !selected.is_empty()) {
+        let suffix = format!(".{name}");
+        if !projected
+            .iter()
+            .any(|(_, alias)| alias == name || alias.ends_with(&suffix))
+        {
+            projected.push((Expr::Attr(name.clone()), name.clone()));
+        }
+    }
+    let carried = projected.len() > selected.len();
+
+    if !projected.is_empty() {
+        plan = LogicalPlan::Projection(columns(projected), plan.into());
+    }
+    if !keys.is_empty() {
+        plan = LogicalPlan::Sort(keys, plan.into());
+    }
+    if carried {
+        plan = LogicalPlan::Projection(columns(selected), plan.into());
+    }
+
+    let limit = match parser.keyword("limit") {
+        true => Some(parser.count()?),
+        false => None,
+    };
+    if parser.keyword("offset") {
+        plan = LogicalPlan::Offset(parser.count()?, plan.into());
+    }
+    if let Some(count) = limit {
+        plan = LogicalPlan::Limit(count, plan.into());
+    }
+
+    Ok(plan)
+}
+
+fn item(parser: &mut Parser) -> Result<Item, Error> {
+    let aggregate = ["count", "sum", "min", "max", "avg"]
+        .iter()
+        .any(|name| parser.is_keyword(name))
+        && *parser.peek_second() == Token::Symbol("(");
+
+    if !aggregate {
+        let (expr, alias) = parser.column()?;
+        return Ok(Item::Column(expr, alias));
+    }
+
+    let agg = parser.aggregate()?;
+    let alias = match parser.keyword("as") {
+        true => Some(parser.ident()?),
+        false => None,
+    };
+
+    Ok(Item::Aggregate(agg, alias))
+}
+
+/// A relation in `FROM` and its alias
+fn relation(parser: &mut Parser) -> Result<LogicalPlan, Error> {
+    let scan = LogicalPlan::Scan(parser.ident()?);
+
+    let aliased = match parser.peek() {
+        Token::Ident(word) => !CLAUSES.contains(&word.to_ascii_lowercase().as_str()),
+        _ => false,
+    };
+    if parser.keyword("as") || aliased {
+        return Ok(LogicalPlan::Rename(parser.ident()?, scan.into()));
+    }
+
+    Ok(scan)
+}
+
+fn create_table(parser: &mut Parser) -> Result<Statement, Error> {
+    parser.expect_keyword("table")?;
+    let name = parser.ident()?;
+
+    parser.expect("(")?;
+    let mut builder = Relation::builder(name);
+    let mut pk = None;
+    loop {
+        let pos = parser.pos();
+        if parser.keyword("primary") {
+            parser.expect_keyword("key")?;
+            parser.expect("(")?;
+            let names = parser.list(Parser::ident)?;
+            parser.expect(")")?;
+            if pk.replace(names).is_some() {
+                return Err(error(pos, "more than one primary key"));
+            }
+        } else {
+            let (attr, primary) = column(parser)?;
+            if primary && pk.replace(vec![attr.name.clone()]).is_some() {
+                return Err(error(pos, "more than one primary key"));
+            }
+            builder = builder.attribute(attr);
+        }
+
+        if !parser.symbol(",") {
+            break;
+        }
+    }
+    parser.expect(")")?;
+
+    for name in pk.into_iter().flatten() {
+        builder = builder.primary_key(name);
+    }
+
+    Ok(Statement::CreateTable(builder.build()?))
+}
+
+fn alter_table(parser: &mut Parser) -> Result<Statement, Error> {
+    parser.expect_keyword("table")?;
+    let name = parser.ident()?;
+
+    let alteration = if parser.keyword("add") {
+        parser.keyword("column");
+        let pos = parser.pos();
+        match column(parser)? {
+            (_, true) => return Err(error(pos, "cannot add a primary key")),
+            (attr, false) => Alteration::AddColumn(attr),
+        }
+    } else if parser.keyword("drop") {
+        parser.keyword("column");
+        Alteration::DropColumn(parser.ident()?)
+    } else if parser.keyword("rename") {
+        parser.keyword("column");
+        let from = parser.ident()?;
+        parser.expect_keyword("to")?;
+        Alteration::RenameColumn(from, parser.ident()?)
+    } else {
+        return Err(parser.unexpected("`ADD`, `DROP` or `RENAME`"));
+    };
+
+    Ok(Statement::AlterTable(name, alteration))
+}
+
+/// The definition of a column, and whether it is the primary key.
+/// Columns are nullable unless they are `NOT NULL`.
+fn column(parser: &mut Parser) -> Result<(Attribute, bool), Error> {
+    let name = parser.ident()?;
+    let atype = column_type(parser)?;
+
+    let (mut nullable, mut primary) = (true, false);
+    loop {
+        if parser.keyword("not") {
+            parser.expect_keyword("null")?;
+            nullable = false;
+        } else if parser.keyword("null") {
+            nullable = true;
+        } else if parser.keyword("primary") {
+            parser.expect_keyword("key")?;
+            primary = true;
+        } else {
+            break;
+        }
+    }
+
+    let attr = Attribute {
+        name,
+        atype,
+        nullable,
+    };
+
+    Ok((attr, primary))
+}
+
+/// A type by its SQL name. Lengths of strings are accepted and ignored,
+/// and a DECIMAL without a precision has the largest one.
+fn column_type(parser: &mut Parser) -> Result<Type, Error> {
+    let pos = parser.pos();
+    let name = parser.ident()?.to_ascii_lowercase();
+
+    let mut args = vec![];
+    if parser.symbol("(") {
+        args = parser.list(Parser::count)?;
+        parser.expect(")")?;
+    }
+
+    let max = Decimal::MAX_PRECISION as usize;
+    let atype = match name.as_str() {
+        "int" | "integer" | "bigint" | "smallint" => Type::Int,
+        "text" | "varchar" | "char" | "string" | "str" => Type::Str,
+        "float" | "real" | "double" => {
+            parser.keyword("precision");
+            Type::Float
+        }
+        "decimal" | "numeric" => match args[..] {
+            [] => Type::Decimal(max as u32, 0),
+            [p] if (1..=max).contains(&p) => Type::Decimal(p as u32, 0),
+            [p, s] if (1..=max).contains(&p) && s <= p => Type::Decimal(p as u32, s as u32),
+            _ => {
+                return Err(error(
+                    pos,
+                    format!("a DECIMAL has a precision from 1 to {max} and a scale up to it"),
+                ));
+            }
+        },
+        "bool" | "boolean" => Type::Bool,
+        "date" => Type::Date,
+        "timestamp" => Type::Timestamp,
+        "bytes" | "blob" | "bytea" => Type::Bytes,
+        _ => return Err(error(pos, format!("unknown type {name}"))),
+    };
+
+    Ok(atype)
+}